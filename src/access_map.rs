@@ -0,0 +1,188 @@
+use egui_macroquad::egui::{self, Color32, Pos2, Rect, Sense, Vec2};
+use crate::bus::{Access, BusObserver};
+use crate::chip8::Chip8;
+use crate::disassembler::disassemble;
+use crate::quirks::Platform;
+
+/// Width of the grid on screen, whatever the memory size
+const GRID_SIZE: f32 = 384.0;
+/// XO-CHIP programs can address 64 KiB, of which only the start is emulated
+const XO_CHIP_ADDRESS_SPACE: usize = 0x10000;
+/// Addresses outside emulated memory
+const UNMAPPED_COLOR: Color32 = Color32::from_gray(40);
+
+/// The instruction that last touched an address
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct LastAccess {
+    pub(crate) pc: u16,
+    pub(crate) opcode: u16,
+    pub(crate) access: Access,
+}
+
+/// Recent reads, writes and executes of every address, fading once per frame
+pub(crate) struct AccessMap {
+    /// Frames for the heat of an access to halve
+    pub(crate) half_life: f32,
+    heat: Vec<[f32; 3]>,
+    last: Vec<Option<LastAccess>>,
+    /// Instruction being executed, put together from the two bytes fetched
+    opcode: u16,
+}
+
+impl Default for AccessMap {
+    fn default() -> Self {
+        Self {
+            half_life: 15.0,
+            heat: vec![[0.0; 3]; 0x1000],
+            last: vec![None; 0x1000],
+            opcode: 0,
+        }
+    }
+}
+
+impl AccessMap {
+    pub(crate) fn clear(&mut self) {
+        self.heat.fill([0.0; 3]);
+        self.last.fill(None);
+    }
+
+    fn record(&mut self, address: usize, access: Access, pc: u16, opcode: u16) {
+        self.heat[address][access as usize] = 1.0;
+        self.last[address] = Some(LastAccess { pc, opcode, access });
+    }
+
+    fn decay(&mut self) {
+        let factor = 0.5f32.powf(1.0 / self.half_life.max(1.0));
+        for heat in self.heat.iter_mut().flatten() {
+            *heat *= factor;
+        }
+    }
+
+    /// Write, execute and read heat of an address, from 0 to 1
+    pub(crate) fn heat(&self, address: usize) -> [f32; 3] {
+        self.heat[address]
+    }
+
+    pub(crate) fn last(&self, address: usize) -> Option<LastAccess> {
+        self.last[address]
+    }
+}
+
+impl BusObserver for AccessMap {
+    fn access(&mut self, address: u16, access: Access, value: u8, pc: u16) {
+        if access == Access::Execute {
+            if address == pc {
+                // Recorded with the second byte, once the whole opcode is known
+                self.opcode = (value as u16) << 8;
+                return;
+            }
+            self.opcode |= value as u16;
+            self.record(pc as usize, access, pc, self.opcode);
+        }
+        self.record(address as usize, access, pc, self.opcode);
+    }
+
+    fn end_frame(&mut self) {
+        self.decay();
+    }
+}
+
+/// Whole address space as a square grid, one cell per byte: 64x64 for 4 KiB, 256x256 for 64 KiB
+#[derive(Default)]
+pub(crate) struct AccessMapView;
+
+impl AccessMapView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("Memory access").open(open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mut enabled = chip8.access_map_enabled();
+                if ui.checkbox(&mut enabled, "Enabled").changed() {
+                    chip8.set_access_map_enabled(enabled);
+                }
+                ui.add(egui::DragValue::new(&mut chip8.access_map_mut().half_life).clamp_range(1.0..=600.0).suffix(" frames"));
+                ui.label("Half-life");
+                if ui.button("Clear").clicked() {
+                    chip8.access_map_mut().clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.colored_label(Color32::RED, "Write");
+                ui.colored_label(Color32::GREEN, "Execute");
+                ui.colored_label(Color32::LIGHT_BLUE, "Read");
+            });
+
+            let mapped = chip8.memory.len();
+            let address_space = match chip8.platform {
+                Platform::XoChip => XO_CHIP_ADDRESS_SPACE,
+                _ => mapped,
+            };
+            let side = (address_space as f32).sqrt() as usize;
+            let cell = GRID_SIZE / side as f32;
+            let (response, painter) = ui.allocate_painter(Vec2::splat(GRID_SIZE), Sense::hover());
+            let origin = response.rect.min;
+            painter.rect_filled(response.rect, 0.0, Color32::BLACK);
+            if mapped < address_space {
+                let top = origin.y + (mapped / side) as f32 * cell;
+                painter.rect_filled(Rect::from_min_max(Pos2::new(origin.x, top), response.rect.max), 0.0, UNMAPPED_COLOR);
+            }
+
+            let map = chip8.access_map();
+            for address in 0..mapped {
+                let [write, execute, read] = map.heat(address).map(|heat| (heat * 255.0) as u8);
+                if write | execute | read == 0 {
+                    continue;
+                }
+                let min = origin + Vec2::new((address % side) as f32, (address / side) as f32) * cell;
+                painter.rect_filled(Rect::from_min_size(min, Vec2::splat(cell)), 0.0, Color32::from_rgb(write, execute, read));
+            }
+
+            if let Some(pointer) = response.hover_pos() {
+                let address = cell_address(pointer - origin, cell, side);
+                let text = if address >= mapped {
+                    format!("{address:04X}\nNot emulated")
+                } else {
+                    match map.last(address) {
+                        Some(last) => format!(
+                            "{address:03X}\nLast {:?} by {:03X}  {}",
+                            last.access, last.pc, disassemble(last.opcode),
+                        ),
+                        None => format!("{address:03X}\nNot accessed"),
+                    }
+                };
+                response.on_hover_text_at_pointer(text);
+            }
+        });
+    }
+}
+
+fn cell_address(offset: Vec2, cell: f32, side: usize) -> usize {
+    let Pos2 { x, y } = (offset / cell).to_pos2();
+    let column = (x as usize).min(side - 1);
+    let row = (y as usize).min(side - 1);
+    row * side + column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heat_fades_and_last_access_is_kept() {
+        let mut map = AccessMap { half_life: 2.0, ..AccessMap::default() };
+        map.access(0x204, Access::Execute, 0xF0, 0x204);
+        map.access(0x205, Access::Execute, 0x55, 0x204);
+        map.access(0x300, Access::Write, 1, 0x204);
+        map.access(0x208, Access::Execute, 0xF0, 0x208);
+        map.access(0x209, Access::Execute, 0x65, 0x208);
+        map.access(0x300, Access::Read, 1, 0x208);
+        map.end_frame();
+        map.end_frame();
+
+        let [write, execute, read] = map.heat(0x300);
+        assert!((write - 0.5).abs() < 1e-5 && (read - 0.5).abs() < 1e-5);
+        assert_eq!(execute, 0.0);
+        assert_eq!(map.last(0x300), Some(LastAccess { pc: 0x208, opcode: 0xF065, access: Access::Read }));
+        assert_eq!(map.last(0x204), Some(LastAccess { pc: 0x204, opcode: 0xF055, access: Access::Execute }));
+        assert_eq!(cell_address(Vec2::new(13.0, 7.0), 6.0, 64), 66);
+    }
+}
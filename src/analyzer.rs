@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+use crate::chip8::START_ADDRESS;
+use crate::quirks::Platform;
+
+/// Programs larger than this don't fit in the 4 KiB address space
+const CHIP8_MAX_ROM_SIZE: usize = 0x1000 - START_ADDRESS;
+/// The COSMAC VIP interpreter reserves 0xEA0..0x1000 for its own use
+const VIP_MAX_ROM_SIZE: usize = 0xEA0 - START_ADDRESS;
+
+/// Result of statically scanning a ROM for platform specific instructions
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RomAnalysis {
+    pub(crate) size: usize,
+    /// Number of instructions reached by following control flow from the entry point
+    pub(crate) reachable: usize,
+    /// Addresses of reachable SUPER-CHIP only instructions
+    pub(crate) schip_opcodes: Vec<(u16, u16)>,
+    /// Addresses of reachable XO-CHIP only instructions
+    pub(crate) xochip_opcodes: Vec<(u16, u16)>,
+    pub(crate) platform: Platform,
+}
+
+fn is_schip_only(opcode: u16) -> bool {
+    matches!(opcode, 0x00FB..=0x00FF)
+        || opcode & 0xFFF0 == 0x00C0
+        || opcode & 0xF00F == 0xD000
+        || matches!(opcode & 0xF0FF, 0xF030 | 0xF075 | 0xF085)
+}
+
+fn is_xochip_only(opcode: u16) -> bool {
+    opcode == 0xF000
+        || opcode == 0xF002
+        || opcode & 0xFFF0 == 0x00D0
+        || matches!(opcode & 0xF00F, 0x5002 | 0x5003)
+        || opcode & 0xF0FF == 0xF001
+        || opcode & 0xF0FF == 0xF03A
+}
+
+/// Follows jumps, calls and skips from the entry point so that sprite data isn't mistaken for code.
+/// Returns the addresses of every reachable instruction.
+fn trace(rom: &[u8]) -> BTreeSet<usize> {
+    let fetch = |address: usize| -> Option<u16> {
+        let offset = address.checked_sub(START_ADDRESS)?;
+        Some(u16::from_be_bytes([*rom.get(offset)?, *rom.get(offset + 1)?]))
+    };
+    // F000 NNNN is the only four byte instruction
+    let length = |address: usize| if fetch(address) == Some(0xF000) { 4 } else { 2 };
+
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![START_ADDRESS];
+
+    while let Some(address) = pending.pop() {
+        let Some(opcode) = fetch(address) else { continue };
+        if !visited.insert(address) {
+            continue;
+        }
+
+        let next = address + length(address);
+        let target = (opcode & 0x0FFF) as usize;
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00EE || opcode == 0x00FD => {} // return, exit
+            0x1 => pending.push(target),
+            0x2 => {
+                pending.push(target);
+                pending.push(next);
+            }
+            0xB => {} // computed jump, can't be followed statically
+            0x3 | 0x4 => {
+                pending.push(next);
+                pending.push(next + length(next));
+            }
+            0x5 | 0x9 if opcode & 0xF == 0 => {
+                pending.push(next);
+                pending.push(next + length(next));
+            }
+            0xE if matches!(opcode & 0xFF, 0x9E | 0xA1) => {
+                pending.push(next);
+                pending.push(next + length(next));
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    visited
+}
+
+pub(crate) fn analyze(rom: &[u8]) -> RomAnalysis {
+    let mut analysis = RomAnalysis {
+        size: rom.len(),
+        ..RomAnalysis::default()
+    };
+
+    let reachable = trace(rom);
+    analysis.reachable = reachable.len();
+
+    for address in reachable {
+        let offset = address - START_ADDRESS;
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+
+        if is_xochip_only(opcode) {
+            analysis.xochip_opcodes.push((address as u16, opcode));
+        } else if is_schip_only(opcode) {
+            analysis.schip_opcodes.push((address as u16, opcode));
+        }
+    }
+
+    analysis.platform = if !analysis.xochip_opcodes.is_empty() || rom.len() > CHIP8_MAX_ROM_SIZE {
+        Platform::XoChip
+    } else if !analysis.schip_opcodes.is_empty() {
+        Platform::SuperChip
+    } else if rom.len() > VIP_MAX_ROM_SIZE {
+        Platform::ModernChip8
+    } else {
+        Platform::OriginalChip8
+    };
+
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_chip8() {
+        // CLS; LD V0, 1; JP 0x202
+        let rom = [0x00, 0xE0, 0x60, 0x01, 0x12, 0x02];
+        assert_eq!(analyze(&rom).platform, Platform::OriginalChip8);
+    }
+
+    #[test]
+    fn schip_hires() {
+        // HIGH; JP 0x202
+        let rom = [0x00, 0xFF, 0x12, 0x02];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.platform, Platform::SuperChip);
+        assert_eq!(analysis.schip_opcodes, vec![(0x200, 0x00FF)]);
+    }
+
+    #[test]
+    fn unreachable_data_is_ignored() {
+        // JP 0x200, followed by sprite data that happens to look like 00FF and F002
+        let rom = [0x12, 0x00, 0x00, 0xFF, 0xF0, 0x02];
+        assert_eq!(analyze(&rom).platform, Platform::OriginalChip8);
+    }
+
+    #[test]
+    fn xochip_long_load() {
+        // LD I, long 0x1234; JP 0x204
+        let rom = [0xF0, 0x00, 0x12, 0x34, 0x12, 0x04];
+        let analysis = analyze(&rom);
+        assert_eq!(analysis.platform, Platform::XoChip);
+        assert_eq!(analysis.reachable, 2);
+    }
+}
@@ -0,0 +1,375 @@
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::ops::{Deref, DerefMut};
+
+pub(crate) const MEMORY_SIZE: usize = 0x1000;
+
+/// Kinds of memory access, in the order of the colour channels the access map draws them in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Access {
+    Write,
+    Execute,
+    Read,
+}
+
+/// Memory as the program sees it. Addresses wrap around the address space.
+pub(crate) trait Bus {
+    /// Reads a byte as data
+    fn read(&mut self, address: u16) -> u8;
+    fn write(&mut self, address: u16, value: u8);
+    /// Reads the instruction at `address`
+    fn fetch(&mut self, address: u16) -> u16;
+}
+
+/// Notified of every access the program makes through the bus
+pub(crate) trait BusObserver: AsAny {
+    /// `pc` is the address of the instruction making the access
+    fn access(&mut self, address: u16, access: Access, value: u8, pc: u16);
+
+    /// Called after every emulated frame
+    fn end_frame(&mut self) {}
+}
+
+/// Lets observers be found by their type
+pub(crate) trait AsAny: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// RAM behind the bus. Dereferences to the raw bytes, for debugger views and loading,
+/// which shouldn't be seen by observers.
+pub(crate) struct Memory {
+    bytes: [u8; MEMORY_SIZE],
+    observers: Vec<Box<dyn BusObserver>>,
+    /// Last fetched instruction, which the following reads and writes are made by
+    pc: u16,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            bytes: [0; MEMORY_SIZE],
+            observers: Vec::new(),
+            pc: 0,
+        }
+    }
+}
+
+impl Deref for Memory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl DerefMut for Memory {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+impl Memory {
+    pub(crate) fn add_observer(&mut self, observer: Box<dyn BusObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Removes every observer of type `T`
+    pub(crate) fn remove_observer<T: BusObserver>(&mut self) {
+        self.observers.retain(|observer| !observer.as_ref().as_any().is::<T>());
+    }
+
+    /// Removes the first observer of type `T` and hands it back
+    pub(crate) fn take_observer<T: BusObserver>(&mut self) -> Option<Box<T>> {
+        let index = self.observers.iter().position(|observer| observer.as_ref().as_any().is::<T>())?;
+        self.observers.remove(index).into_any().downcast().ok()
+    }
+
+    pub(crate) fn observer<T: BusObserver>(&self) -> Option<&T> {
+        self.observers.iter().find_map(|observer| observer.as_ref().as_any().downcast_ref())
+    }
+
+    pub(crate) fn observer_mut<T: BusObserver>(&mut self) -> Option<&mut T> {
+        self.observers.iter_mut().find_map(|observer| observer.as_mut().as_any_mut().downcast_mut())
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        for observer in &mut self.observers {
+            observer.end_frame();
+        }
+    }
+
+    #[inline]
+    fn notify(&mut self, address: usize, access: Access, value: u8) {
+        for observer in &mut self.observers {
+            observer.access(address as u16, access, value, self.pc);
+        }
+    }
+}
+
+impl Bus for Memory {
+    #[inline]
+    fn read(&mut self, address: u16) -> u8 {
+        let address = address as usize % MEMORY_SIZE;
+        let value = self.bytes[address];
+        self.notify(address, Access::Read, value);
+        value
+    }
+
+    #[inline]
+    fn write(&mut self, address: u16, value: u8) {
+        let address = address as usize % MEMORY_SIZE;
+        self.bytes[address] = value;
+        self.notify(address, Access::Write, value);
+    }
+
+    #[inline]
+    fn fetch(&mut self, address: u16) -> u16 {
+        self.pc = address;
+        let hi = address as usize % MEMORY_SIZE;
+        let lo = (hi + 1) % MEMORY_SIZE;
+        self.notify(hi, Access::Execute, self.bytes[hi]);
+        self.notify(lo, Access::Execute, self.bytes[lo]);
+        u16::from_be_bytes([self.bytes[hi], self.bytes[lo]])
+    }
+}
+
+/// An observer that can be switched off. It's only on the bus while enabled, so that a disabled tool
+/// costs nothing per access, and is kept here with its data while it's off.
+pub(crate) struct Tool<T: BusObserver> {
+    /// `None` while the observer is on the bus
+    parked: Option<Box<T>>,
+}
+
+impl<T: BusObserver + Default> Default for Tool<T> {
+    fn default() -> Self {
+        Self { parked: Some(Box::default()) }
+    }
+}
+
+impl<T: BusObserver> Tool<T> {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.parked.is_none()
+    }
+
+    pub(crate) fn set_enabled(&mut self, memory: &mut Memory, enabled: bool) {
+        if enabled == self.is_enabled() {
+            return;
+        }
+        match self.parked.take() {
+            Some(observer) => memory.add_observer(observer),
+            None => self.parked = memory.take_observer(),
+        }
+    }
+
+    pub(crate) fn get<'a>(&'a self, memory: &'a Memory) -> &'a T {
+        match self.parked {
+            Some(ref observer) => observer,
+            None => memory.observer().expect("an enabled tool is on the bus"),
+        }
+    }
+
+    pub(crate) fn get_mut<'a>(&'a mut self, memory: &'a mut Memory) -> &'a mut T {
+        match self.parked {
+            Some(ref mut observer) => observer,
+            None => memory.observer_mut().expect("an enabled tool is on the bus"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Watch {
+    Read,
+    Write,
+    /// Reads and writes
+    Access,
+}
+
+impl Watch {
+    pub(crate) const ALL: [Watch; 3] = [Watch::Read, Watch::Write, Watch::Access];
+
+    fn matches(&self, access: Access) -> bool {
+        matches!(
+            (self, access),
+            (Watch::Read | Watch::Access, Access::Read) | (Watch::Write | Watch::Access, Access::Write)
+        )
+    }
+}
+
+impl Display for Watch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Watch::Read => write!(f, "Read"),
+            Watch::Write => write!(f, "Write"),
+            Watch::Access => write!(f, "Read/write"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct WatchHit {
+    pub(crate) address: u16,
+    pub(crate) access: Access,
+    pub(crate) value: u8,
+    pub(crate) pc: u16,
+}
+
+impl Display for WatchHit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.access {
+            Access::Write => write!(f, "{:03X} wrote {:02X} to {:03X}", self.pc, self.value, self.address),
+            _ => write!(f, "{:03X} read {:02X} from {:03X}", self.pc, self.value, self.address),
+        }
+    }
+}
+
+/// Addresses that pause emulation when the program reads or writes them.
+/// Only on the bus while there are any.
+#[derive(Default)]
+pub(crate) struct Watchpoints {
+    pub(crate) addresses: BTreeMap<u16, Watch>,
+    hit: Option<WatchHit>,
+}
+
+impl Watchpoints {
+    /// First access since the last call that matched a watchpoint
+    pub(crate) fn take_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+}
+
+impl BusObserver for Watchpoints {
+    fn access(&mut self, address: u16, access: Access, value: u8, pc: u16) {
+        let watched = self.addresses.get(&address).is_some_and(|watch| watch.matches(access));
+        if watched && self.hit.is_none() {
+            self.hit = Some(WatchHit { address, access, value, pc });
+        }
+    }
+}
+
+/// Addresses written since the emulator last advanced, for highlighting in the RAM view
+#[derive(Default)]
+pub(crate) struct RecentWrites {
+    addresses: BTreeSet<u16>,
+}
+
+impl RecentWrites {
+    pub(crate) fn contains(&self, address: u16) -> bool {
+        self.addresses.contains(&address)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.addresses.clear();
+    }
+}
+
+impl BusObserver for RecentWrites {
+    fn access(&mut self, address: u16, access: Access, _value: u8, _pc: u16) {
+        if access == Access::Write {
+            self.addresses.insert(address);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observers_see_accesses_with_the_instruction_making_them() {
+        let mut memory = Memory::default();
+        memory[0x200] = 0xF0;
+        memory[0x201] = 0x55;
+        assert_eq!(memory.fetch(0x200), 0xF055);
+        memory.write(0x300, 7);
+
+        let mut watchpoints = Watchpoints::default();
+        watchpoints.addresses.insert(0x300, Watch::Write);
+        watchpoints.addresses.insert(0x301, Watch::Read);
+        memory.add_observer(Box::new(watchpoints));
+
+        memory.fetch(0x202);
+        assert_eq!(memory.read(0x300), 7);
+        assert_eq!(memory.observer_mut::<Watchpoints>().unwrap().take_hit(), None);
+        memory.write(0x1300, 9);
+        memory.read(0x301);
+        let hit = memory.observer_mut::<Watchpoints>().unwrap().take_hit();
+        assert_eq!(hit, Some(WatchHit { address: 0x300, access: Access::Write, value: 9, pc: 0x202 }));
+
+        memory.remove_observer::<Watchpoints>();
+        assert!(memory.observer::<Watchpoints>().is_none());
+    }
+
+    #[test]
+    fn disabled_tools_are_off_the_bus_and_keep_their_data() {
+        let mut memory = Memory::default();
+        let mut tool = Tool::<Watchpoints>::default();
+        tool.get_mut(&mut memory).addresses.insert(0x300, Watch::Write);
+        assert!(!tool.is_enabled());
+        memory.write(0x300, 1);
+        assert_eq!(tool.get_mut(&mut memory).take_hit(), None);
+
+        tool.set_enabled(&mut memory, true);
+        memory.write(0x300, 2);
+        tool.set_enabled(&mut memory, false);
+        assert!(memory.observer::<Watchpoints>().is_none());
+        assert_eq!(tool.get_mut(&mut memory).take_hit().map(|hit| hit.value), Some(2));
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, ignore = "timing is only meaningful with optimizations")]
+    fn bus_without_observers_costs_no_more_than_indexing() {
+        use std::hint::black_box;
+        use std::time::{Duration, Instant};
+
+        const ACCESSES: u32 = 1 << 20;
+
+        fn best_of(mut run: impl FnMut()) -> Duration {
+            (0..20)
+                .map(|_| {
+                    let start = Instant::now();
+                    run();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        }
+
+        let mut memory = Memory::default();
+        let bus = best_of(|| {
+            for i in 0..ACCESSES {
+                let address = black_box(i as u16);
+                let value = memory.read(address);
+                memory.write(address.wrapping_add(1), value ^ 1);
+            }
+        });
+        let mut bytes = [0; MEMORY_SIZE];
+        let indexing = best_of(|| {
+            for i in 0..ACCESSES {
+                let address = black_box(i as u16) as usize;
+                let value = bytes[address % MEMORY_SIZE];
+                bytes[(address + 1) % MEMORY_SIZE] = value ^ 1;
+            }
+        });
+        black_box((&memory.bytes, &bytes));
+
+        // Some slack for noise on a busy machine
+        assert!(bus <= indexing * 3 / 2 + Duration::from_micros(50), "bus {bus:?}, indexing {indexing:?}");
+    }
+}
@@ -0,0 +1,200 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, ImageError, Rgba, RgbaImage};
+use macroquad::input::{is_key_pressed, KeyCode};
+use thiserror::Error;
+use crate::screen::{Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+const CAPTURE_DIRECTORY: &str = "captures";
+/// The main loop runs at the display refresh rate, which is assumed to be 60 Hz
+const FRAMES_PER_SECOND: u32 = 60;
+
+#[derive(Error, Debug)]
+pub(crate) enum CaptureError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("image error: {0}")]
+    Image(#[from] ImageError),
+}
+
+type CaptureResult<T> = Result<T, CaptureError>;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum RecordingFormat {
+    #[default]
+    Gif,
+    /// Headerless RGBA frames, for piping into an encoder such as ffmpeg
+    RawFrames,
+}
+
+enum Recording {
+    Gif {
+        encoder: GifEncoder<BufWriter<File>>,
+        /// Last captured frame and for how many display frames it has been shown.
+        /// Identical frames are merged into one longer GIF frame.
+        pending: Option<(RgbaImage, u32)>,
+    },
+    RawFrames {
+        writer: BufWriter<File>,
+        size: (u32, u32),
+    },
+}
+
+/// Screenshots and recordings of the emulated screen. The side panel is never captured.
+pub(crate) struct Capture {
+    /// Every CHIP-8 pixel becomes a `scale` x `scale` square
+    pub(crate) scale: u32,
+    pub(crate) format: RecordingFormat,
+    recording: Option<(Recording, PathBuf)>,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            scale: 8,
+            format: RecordingFormat::default(),
+            recording: None,
+        }
+    }
+}
+
+impl Capture {
+    /// Handles the screenshot (F12) and record (F10) hotkeys and records the current frame
+    pub(crate) fn update(&mut self, screen: &Screen) {
+        if is_key_pressed(KeyCode::F12) {
+            self.screenshot(screen);
+        }
+        if is_key_pressed(KeyCode::F10) {
+            self.toggle_recording();
+        }
+
+        if let Some((ref mut recording, _)) = self.recording {
+            let frame = render(screen, self.scale);
+            if let Err(e) = recording.push(frame) {
+                log::error!("Recording failed: {e}");
+                self.stop_recording();
+            }
+        }
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub(crate) fn screenshot(&self, screen: &Screen) {
+        let result = capture_path("png").and_then(|path| {
+            render(screen, self.scale).save(&path)?;
+            Ok(path)
+        });
+        match result {
+            Ok(path) => log::info!("Saved screenshot to {}", path.display()),
+            Err(e) => log::error!("Failed to save screenshot: {e}"),
+        }
+    }
+
+    pub(crate) fn toggle_recording(&mut self) {
+        if self.is_recording() {
+            self.stop_recording();
+        } else {
+            match self.start_recording() {
+                Ok(path) => log::info!("Recording to {}", path.display()),
+                Err(e) => log::error!("Failed to start recording: {e}"),
+            }
+        }
+    }
+
+    fn start_recording(&mut self) -> CaptureResult<PathBuf> {
+        let (recording, path) = match self.format {
+            RecordingFormat::Gif => {
+                let path = capture_path("gif")?;
+                let mut encoder = GifEncoder::new(BufWriter::new(File::create(&path)?));
+                encoder.set_repeat(Repeat::Infinite)?;
+                (Recording::Gif { encoder, pending: None }, path)
+            }
+            RecordingFormat::RawFrames => {
+                let path = capture_path("rgba")?;
+                let size = (SCREEN_WIDTH as u32 * self.scale, SCREEN_HEIGHT as u32 * self.scale);
+                (Recording::RawFrames { writer: BufWriter::new(File::create(&path)?), size }, path)
+            }
+        };
+
+        self.recording = Some((recording, path.clone()));
+        Ok(path)
+    }
+
+    fn stop_recording(&mut self) {
+        let Some((recording, path)) = self.recording.take() else {
+            return;
+        };
+
+        match recording.finish() {
+            Ok(Some((width, height))) => log::info!(
+                "Saved recording to {0}, encode it with: ffmpeg -f rawvideo -pix_fmt rgba -s {width}x{height} -r 60 -i {0} out.mp4",
+                path.display()
+            ),
+            Ok(None) => log::info!("Saved recording to {}", path.display()),
+            Err(e) => log::error!("Failed to finish recording: {e}"),
+        }
+    }
+}
+
+impl Recording {
+    fn push(&mut self, frame: RgbaImage) -> CaptureResult<()> {
+        match self {
+            Recording::Gif { encoder, pending } => match pending {
+                Some((image, frames)) if *image == frame => *frames += 1,
+                _ => {
+                    if let Some((image, frames)) = pending.replace((frame, 1)) {
+                        write_gif_frame(encoder, image, frames)?;
+                    }
+                }
+            },
+            Recording::RawFrames { writer, .. } => writer.write_all(frame.as_raw())?,
+        }
+        Ok(())
+    }
+
+    /// Flushes the recording. Returns the frame size for raw recordings, which an encoder needs to know.
+    fn finish(self) -> CaptureResult<Option<(u32, u32)>> {
+        match self {
+            Recording::Gif { mut encoder, pending } => {
+                if let Some((image, frames)) = pending {
+                    write_gif_frame(&mut encoder, image, frames)?;
+                }
+                Ok(None)
+            }
+            Recording::RawFrames { mut writer, size } => {
+                writer.flush()?;
+                Ok(Some(size))
+            }
+        }
+    }
+}
+
+fn write_gif_frame(encoder: &mut GifEncoder<BufWriter<File>>, image: RgbaImage, frames: u32) -> CaptureResult<()> {
+    let delay = Delay::from_numer_denom_ms(frames * 1000, FRAMES_PER_SECOND);
+    encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))?;
+    Ok(())
+}
+
+/// Draws the screen with its palette, without display filters or shaders
+pub(crate) fn render(screen: &Screen, scale: u32) -> RgbaImage {
+    let scale = scale.max(1);
+    RgbaImage::from_fn(SCREEN_WIDTH as u32 * scale, SCREEN_HEIGHT as u32 * scale, |x, y| {
+        let on = screen.state[(y / scale) as usize][(x / scale) as usize];
+        let color: [u8; 4] = screen.palette.colors[on as usize].into();
+        Rgba(color)
+    })
+}
+
+/// A new file in the capture directory, named after the current time
+fn capture_path(extension: &str) -> CaptureResult<PathBuf> {
+    let directory = Path::new(CAPTURE_DIRECTORY);
+    fs::create_dir_all(directory)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    Ok(directory.join(format!("chipmate-{timestamp}.{extension}")))
+}
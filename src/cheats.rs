@@ -0,0 +1,275 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use egui_macroquad::egui;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::chip8::Chip8;
+use crate::savestate::SAVE_DIRECTORY;
+
+/// Candidates listed in the search results, the rest are only counted
+const MAX_CANDIDATES_SHOWN: usize = 100;
+
+#[derive(Error, Debug)]
+pub(crate) enum CheatError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid cheat list: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Keeps a byte of memory at a fixed value
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Cheat {
+    pub(crate) address: u16,
+    pub(crate) value: u8,
+    pub(crate) enabled: bool,
+    pub(crate) description: String,
+}
+
+/// Cheats of the loaded ROM, stored next to its save states
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CheatList {
+    pub(crate) cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub(crate) fn path(rom_hash: &str) -> PathBuf {
+        Path::new(SAVE_DIRECTORY).join(rom_hash).join("cheats.json")
+    }
+
+    /// Cheats saved for a ROM, none if there's no list yet
+    pub(crate) fn load(rom_hash: &str) -> Result<CheatList, CheatError> {
+        let path = CheatList::path(rom_hash);
+        if !path.exists() {
+            return Ok(CheatList::default());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub(crate) fn save(&self, rom_hash: &str) -> Result<(), CheatError> {
+        let path = CheatList::path(rom_hash);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Writes the frozen values, called once per frame
+    pub(crate) fn apply(&self, memory: &mut [u8]) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            if let Some(byte) = memory.get_mut(cheat.address as usize) {
+                *byte = cheat.value;
+            }
+        }
+    }
+}
+
+/// How a candidate's value must compare to the previous search
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Filter {
+    #[default]
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u8),
+}
+
+impl Filter {
+    pub(crate) const ALL: [Filter; 5] = [Filter::Equal, Filter::Changed, Filter::Increased, Filter::Decreased, Filter::Value(0)];
+
+    fn matches(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            Filter::Equal => current == previous,
+            Filter::Changed => current != previous,
+            Filter::Increased => current > previous,
+            Filter::Decreased => current < previous,
+            Filter::Value(value) => current == value,
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Filter::Equal => write!(f, "Equal"),
+            Filter::Changed => write!(f, "Changed"),
+            Filter::Increased => write!(f, "Increased"),
+            Filter::Decreased => write!(f, "Decreased"),
+            Filter::Value(_) => write!(f, "Value"),
+        }
+    }
+}
+
+/// Narrows down the addresses of a game variable over successive searches
+#[derive(Default)]
+pub(crate) struct CheatSearch {
+    /// Addresses still matching every filter, none before the first snapshot
+    candidates: Vec<usize>,
+    /// Memory at the last search
+    previous: Vec<u8>,
+}
+
+impl CheatSearch {
+    /// Starts over with every address as a candidate
+    pub(crate) fn start(&mut self, memory: &[u8]) {
+        self.candidates = (0..memory.len()).collect();
+        self.previous = memory.to_vec();
+    }
+
+    pub(crate) fn filter(&mut self, memory: &[u8], filter: Filter) {
+        let previous = &self.previous;
+        self.candidates.retain(|&address| filter.matches(previous[address], memory[address]));
+        self.previous = memory.to_vec();
+    }
+
+    pub(crate) fn is_started(&self) -> bool {
+        !self.previous.is_empty()
+    }
+
+    pub(crate) fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+
+    /// Value of an address at the last search
+    pub(crate) fn previous(&self, address: usize) -> u8 {
+        self.previous[address]
+    }
+}
+
+/// Cheat search and the frozen addresses of the loaded ROM
+#[derive(Default)]
+pub(crate) struct CheatView {
+    search: CheatSearch,
+    filter: Filter,
+}
+
+impl CheatView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("Cheats").open(open).show(ctx, |ui| {
+            let mut changed = false;
+            ui.collapsing("Search", |ui| changed |= self.search_ui(ui, chip8));
+            ui.collapsing("Frozen addresses", |ui| changed |= cheats_ui(ui, &mut chip8.cheats));
+
+            if changed {
+                match chip8.rom_hash {
+                    Some(ref hash) => {
+                        if let Err(e) = chip8.cheats.save(hash) {
+                            log::error!("{e}");
+                        }
+                    }
+                    None => log::warn!("No ROM loaded, cheats won't be saved"),
+                }
+            }
+        });
+    }
+
+    /// Returns true if a cheat was added
+    fn search_ui(&mut self, ui: &mut egui::Ui, chip8: &mut Chip8) -> bool {
+        ui.horizontal(|ui| {
+            if ui.button("New search").on_hover_text("Snapshots memory").clicked() {
+                self.search.start(&chip8.memory);
+            }
+            egui::ComboBox::from_id_source("cheat_filter")
+                .selected_text(self.filter.to_string())
+                .show_ui(ui, |ui| {
+                    for option in Filter::ALL {
+                        let selected = std::mem::discriminant(&self.filter) == std::mem::discriminant(&option);
+                        if ui.selectable_label(selected, option.to_string()).clicked() && !selected {
+                            self.filter = option;
+                        }
+                    }
+                });
+            if let Filter::Value(ref mut value) = self.filter {
+                ui.add(egui::DragValue::new(value).hexadecimal(2, false, true));
+            }
+            if ui.add_enabled(self.search.is_started(), egui::Button::new("Filter")).clicked() {
+                self.search.filter(&chip8.memory, self.filter);
+            }
+        });
+        if !self.search.is_started() {
+            return false;
+        }
+
+        let candidates = self.search.candidates();
+        ui.label(format!("{} candidates", candidates.len()));
+        let mut added = false;
+        egui::ScrollArea::vertical().id_source("cheat_candidates").max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("cheat_candidates").striped(true).show(ui, |ui| {
+                for &address in candidates.iter().take(MAX_CANDIDATES_SHOWN) {
+                    ui.monospace(format!("{address:03X}"));
+                    ui.monospace(format!("{:02X} → {:02X}", self.search.previous(address), chip8.memory[address]));
+                    if ui.small_button("Freeze").clicked() {
+                        let value = chip8.memory[address];
+                        match chip8.cheats.cheats.iter_mut().find(|cheat| cheat.address as usize == address) {
+                            Some(cheat) => {
+                                cheat.value = value;
+                                cheat.enabled = true;
+                            }
+                            None => chip8.cheats.cheats.push(Cheat {
+                                address: address as u16,
+                                value,
+                                enabled: true,
+                                description: String::new(),
+                            }),
+                        }
+                        added = true;
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        added
+    }
+}
+
+/// Returns true if the list was edited
+fn cheats_ui(ui: &mut egui::Ui, list: &mut CheatList) -> bool {
+    let mut changed = false;
+    let mut removed = None;
+    egui::Grid::new("cheats").striped(true).show(ui, |ui| {
+        for (i, cheat) in list.cheats.iter_mut().enumerate() {
+            changed |= ui.checkbox(&mut cheat.enabled, "").changed();
+            changed |= ui.add(egui::DragValue::new(&mut cheat.address).hexadecimal(3, false, true).clamp_range(0..=0xFFF)).changed();
+            changed |= ui.add(egui::DragValue::new(&mut cheat.value).hexadecimal(2, false, true)).changed();
+            changed |= ui.add(egui::TextEdit::singleline(&mut cheat.description).hint_text("Description").desired_width(120.0)).lost_focus();
+            if ui.small_button("Remove").clicked() {
+                removed = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = removed {
+        list.cheats.remove(i);
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn narrows_down_a_counter_and_freezes_it() {
+        let mut memory = vec![0u8; 16];
+        memory[3] = 5;
+        memory[9] = 5;
+        let mut search = CheatSearch::default();
+        search.start(&memory);
+
+        memory[3] = 4;
+        memory[7] = 1;
+        search.filter(&memory, Filter::Decreased);
+        assert_eq!(search.candidates(), [3]);
+        search.filter(&memory, Filter::Equal);
+        search.filter(&memory, Filter::Value(4));
+        assert_eq!(search.candidates(), [3]);
+
+        let list = CheatList { cheats: vec![Cheat { address: 3, value: 9, enabled: true, description: "Lives".to_owned() }] };
+        list.apply(&mut memory);
+        assert_eq!(memory[3], 9);
+    }
+}
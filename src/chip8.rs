@@ -1,14 +1,30 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 use macroquad::input::{is_key_down, is_key_pressed};
 use macroquad::prelude::{get_keys_down, KeyCode::*};
 use rand::random;
 use thiserror::Error;
+use crate::access_map::AccessMap;
+use crate::analyzer::{analyze, RomAnalysis};
+use crate::bus::{Bus, Memory, RecentWrites, Tool, Watch, WatchHit, Watchpoints};
+use crate::cheats::CheatList;
+use crate::coverage::Coverage;
+use crate::database::{sha1_hex, Database, RomProfile};
+use crate::history::{History, HistoryEntry, Registers};
+use crate::movie::{keypad_to_mask, mask_to_keypad, Movie, MovieState};
+use crate::patch::{self, PatchError};
+use crate::profiler::Profiler;
+use crate::quirks::{Platform, Quirks};
+use crate::rng::{RandomSource, RngMode};
+use crate::savestate::{pack_row, slot_path, unpack_row, SaveState, SaveStateError};
 use crate::screen::{Screen, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::smc::SmcDetector;
+use crate::speed::{Advance, RunState, SpeedControl};
+use crate::timeline::Timeline;
 
-const START_ADDRESS: usize = 0x200;
-const FONTSET_START_ADDRESS: usize = 0x50;
+pub(crate) const START_ADDRESS: usize = 0x200;
+pub(crate) const FONTSET_START_ADDRESS: usize = 0x50;
 const FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -27,22 +43,68 @@ const FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
+pub(crate) const FONTSET_END_ADDRESS: usize = FONTSET_START_ADDRESS + FONTSET.len();
 
 pub(crate) struct Chip8 {
     pub(crate) registers: [u8; 16],
-    pub(crate) memory: [u8; 4096],
+    pub(crate) memory: Memory,
     index: u16,
     pc: u16,
     stack: [u16; 16],
     stack_ptr: u8,
     delay_timer: u8,
     sound_timer: u8,
-    keypad: [bool; 16],
+    pub(crate) keypad: [bool; 16],
     pub(crate) screen: Screen,
     pub(crate) opcode: u16,
-    pub(crate) cycle_advance: bool,
-    pub(crate) block_cycle: bool,
-    pub(crate) shift_quirk: bool
+    pub(crate) speed: SpeedControl,
+    pub(crate) platform: Platform,
+    pub(crate) quirks: Quirks,
+    /// Instructions executed per frame
+    pub(crate) tickrate: u32,
+    /// Instructions executed since the timers last ticked
+    frame_cycles: u32,
+    /// Set by DXYN when the vblank quirk is on, ends the current frame early
+    vblank_wait: bool,
+    /// SHA-1 of the loaded ROM
+    pub(crate) rom_hash: Option<String>,
+    /// Database entry for the loaded ROM, if it's known
+    pub(crate) rom_profile: Option<RomProfile>,
+    /// Platform guess from scanning the loaded ROM
+    pub(crate) rom_analysis: Option<RomAnalysis>,
+    /// Apply the database profile when a ROM is loaded
+    pub(crate) auto_profile: bool,
+    /// The loaded ROM, kept so the program can be restarted without reading it again
+    pub(crate) rom: Vec<u8>,
+    /// The ROM as read from its file, before any patch. Patches are created against it.
+    pub(crate) rom_original: Vec<u8>,
+    /// File the ROM was loaded from
+    pub(crate) rom_file: Option<PathBuf>,
+    /// Apply an IPS or BPS patch with the same name as the ROM when loading it
+    pub(crate) auto_patch: bool,
+    /// Patch applied to the loaded ROM
+    pub(crate) patch_file: Option<PathBuf>,
+    /// Seed CXNN's random number generator is reset to
+    pub(crate) seed: u64,
+    rng: RandomSource,
+    /// Instructions executed since the last reset
+    pub(crate) cycles: u64,
+    pub(crate) movie: MovieState,
+    /// Whether the last played movie ended in the recorded state
+    pub(crate) movie_verified: Option<bool>,
+    /// Quick save slot used by F5/F9
+    pub(crate) state_slot: u8,
+    /// Addresses that pause emulation when PC reaches them. Survive resets.
+    pub(crate) breakpoints: BTreeSet<u16>,
+    pub(crate) profiler: Profiler,
+    pub(crate) history: History,
+    pub(crate) timeline: Timeline,
+    /// Frozen addresses of the loaded ROM, applied at the end of every frame
+    pub(crate) cheats: CheatList,
+    coverage: Tool<Coverage>,
+    smc: Tool<SmcDetector>,
+    access_map: Tool<AccessMap>,
+    recent_writes: Tool<RecentWrites>,
 }
 
 #[derive(Error, Debug)]
@@ -50,15 +112,20 @@ pub(crate) enum Chip8Error {
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
     #[error("unimplemented opcode {0:X}")]
-    UnimplementedOpcode(u16)
+    UnimplementedOpcode(u16),
+    #[error("ROM is too large ({0} bytes)")]
+    RomTooLarge(usize),
+    #[error("couldn't apply patch: {0}")]
+    Patch(#[from] PatchError),
 }
 
 type Chip8Result<T> = Result<T, Chip8Error>;
 
 impl Default for Chip8 {
     fn default() -> Self {
-        let mut memory: [u8; 4096] = [0; 4096];
+        let mut memory = Memory::default();
         memory[FONTSET_START_ADDRESS..(FONTSET.len() + FONTSET_START_ADDRESS)].copy_from_slice(&FONTSET[..]);
+        let seed = random();
 
 
         Self {
@@ -73,48 +140,257 @@ impl Default for Chip8 {
             keypad: [false; 16],
             screen: Screen::default(),
             opcode: 0,
-            cycle_advance: false,
-            block_cycle: true,
-            shift_quirk: false,
+            speed: SpeedControl::default(),
+            platform: Platform::default(),
+            quirks: Platform::default().quirks(),
+            tickrate: Platform::default().default_tickrate(),
+            frame_cycles: 0,
+            vblank_wait: false,
+            rom_hash: None,
+            rom_profile: None,
+            rom_analysis: None,
+            auto_profile: true,
+            rom: Vec::new(),
+            rom_original: Vec::new(),
+            rom_file: None,
+            auto_patch: true,
+            patch_file: None,
+            seed,
+            rng: RandomSource::new(RngMode::default(), seed),
+            cycles: 0,
+            movie: MovieState::Idle,
+            movie_verified: None,
+            state_slot: 1,
+            breakpoints: BTreeSet::new(),
+            profiler: Profiler::default(),
+            history: History::default(),
+            timeline: Timeline::default(),
+            cheats: CheatList::default(),
+            coverage: Tool::default(),
+            smc: Tool::default(),
+            access_map: Tool::default(),
+            recent_writes: Tool::default(),
         }
     }
 }
 
 impl Chip8 {
     pub(crate) fn reset(&mut self) {
-        let mut memory: [u8; 4096] = [0; 4096];
+        // Observers stay on the bus, like breakpoints
+        self.memory.fill(0);
+        self.memory[FONTSET_START_ADDRESS..(FONTSET.len() + FONTSET_START_ADDRESS)].copy_from_slice(&FONTSET[..]);
 
-        memory[FONTSET_START_ADDRESS..(FONTSET.len() + FONTSET_START_ADDRESS)].copy_from_slice(&FONTSET[..]);
-
-        self.memory = memory;
         self.registers = [0; 16];
         self.pc = 0x200;
         self.index = 0;
         self.stack = [0; 16];
         self.stack_ptr = 0;
+        self.profiler.sync_stack(&[], &self.memory);
+        self.smc_mut().clear();
+        self.access_map_mut().clear();
+        self.history.clear();
+        self.timeline.clear();
         self.delay_timer = 0;
         self.sound_timer = 0;
         self.keypad = [false; 16];
         self.opcode = 0;
+        self.frame_cycles = 0;
+        self.vblank_wait = false;
+        self.cycles = 0;
+        self.rng = RandomSource::new(self.rng.mode(), self.seed);
 
         self.screen.reset();
     }
 
-    pub(crate) fn load_rom(&mut self, path: &PathBuf) -> Chip8Result<()> {
-        let mut file = File::open(path.clone())?;
-        let mut buffer: [u8; 4096] = [0; 4096];
-        let size = file.metadata()?.len() as usize;
+    /// Resets the emulator and reloads the current ROM from memory
+    pub(crate) fn restart(&mut self) {
+        self.reset();
+        self.copy_rom_to_memory();
+    }
+
+    /// Writes the loaded ROM to program memory, leaving everything else untouched
+    pub(crate) fn copy_rom_to_memory(&mut self) {
+        self.memory[START_ADDRESS..(self.rom.len() + START_ADDRESS)].copy_from_slice(&self.rom);
+    }
+
+    pub(crate) fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub(crate) fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Return addresses currently on the stack, oldest first
+    pub(crate) fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_ptr as usize]
+    }
+
+    pub(crate) fn load_rom(&mut self, path: &Path) -> Chip8Result<()> {
+        let patch = if self.auto_patch { patch::find_patch(path) } else { None };
+        self.load_rom_with_patch(path, patch.as_deref())
+    }
+
+    pub(crate) fn load_rom_with_patch(&mut self, path: &Path, patch: Option<&Path>) -> Chip8Result<()> {
+        let original = fs::read(path)?;
+        let rom = match patch {
+            Some(patch) => {
+                let rom = patch::apply(&original, &fs::read(patch)?)?;
+                log::info!("Applied patch {}", patch.display());
+                rom
+            }
+            None => original.clone(),
+        };
+        let size = rom.len();
+
+        if size > self.memory.len() - START_ADDRESS {
+            return Err(Chip8Error::RomTooLarge(size));
+        }
+
+        self.memory[START_ADDRESS..(size + START_ADDRESS)].copy_from_slice(&rom);
+
+        if !self.movie.is_idle() {
+            log::warn!("Loaded a new ROM, stopping movie");
+            self.movie = MovieState::Idle;
+        }
+
+        let hash = sha1_hex(&rom);
+        self.rom_profile = Database::get().lookup(&hash).cloned();
+        match self.rom_profile {
+            Some(ref profile) => log::info!("Found '{}' ({}) in ROM database", profile.title, profile.platform),
+            None => log::info!("ROM {hash} is not in the database"),
+        }
+        self.cheats = CheatList::load(&hash).unwrap_or_else(|e| {
+            log::error!("{e}");
+            CheatList::default()
+        });
+        self.rom_hash = Some(hash);
+
+        let analysis = analyze(&rom);
+        log::info!("ROM looks like a {} program", analysis.platform);
+        self.rom_analysis = Some(analysis);
+
+        self.rom = rom;
+        self.rom_original = original;
+        self.rom_file = Some(path.to_path_buf());
+        self.patch_file = patch.map(Path::to_path_buf);
+
+        if self.auto_profile {
+            self.apply_profile();
+        }
+
+        log::info!("Successfully loaded {}", path.display());
+        Ok(())
+    }
+
+    /// Applies platform, quirks, tickrate and colours from the database profile of the loaded ROM
+    pub(crate) fn apply_profile(&mut self) {
+        let Some(ref profile) = self.rom_profile else {
+            return;
+        };
+
+        self.platform = profile.platform;
+        self.quirks = profile.quirks;
+        self.tickrate = profile.tickrate;
+        if profile.colors.len() >= 2 {
+            let palette = &mut self.screen.palette;
+            palette.name = profile.title.clone();
+            for (slot, &color) in palette.colors.iter_mut().zip(&profile.colors) {
+                *slot = color;
+            }
+        }
+    }
+
+    pub(crate) fn rng_mode(&self) -> RngMode {
+        self.rng.mode()
+    }
 
-        #[allow(clippy::unused_io_amount)] // It's fine if we have some buffer space left
-        file.read(&mut buffer)?;
+    /// Reseeds CXNN's random number generator, immediately and on every reset
+    pub(crate) fn set_rng(&mut self, mode: RngMode, seed: u64) {
+        self.seed = seed;
+        self.rng = RandomSource::new(mode, seed);
+    }
 
+    pub(crate) fn save_state(&self) -> SaveState {
+        SaveState {
+            rom_hash: self.rom_hash.clone().unwrap_or_else(|| sha1_hex(&self.rom)),
+            registers: self.registers,
+            memory: self.memory.to_vec(),
+            index: self.index,
+            pc: self.pc,
+            stack: self.stack,
+            stack_ptr: self.stack_ptr,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            screen: self.screen.state.iter().map(|row| pack_row(row)).collect(),
+            cycles: self.cycles,
+            frame_cycles: self.frame_cycles,
+            vblank_wait: self.vblank_wait,
+            seed: self.seed,
+            rng: self.rng.clone(),
+            platform: self.platform,
+            quirks: self.quirks,
+            tickrate: self.tickrate,
+        }
+    }
 
-        self.memory[START_ADDRESS..(size + START_ADDRESS)].copy_from_slice(&buffer[..size]);
+    pub(crate) fn load_state(&mut self, state: &SaveState) {
+        if state.rom_hash != sha1_hex(&self.rom) {
+            log::warn!("Save state was made with a different ROM ({})", state.rom_hash);
+        }
 
-        log::info!("Successfully loaded {}", path.to_str().unwrap());
+        self.restore(state);
+        self.profiler.sync_stack(&self.stack[..self.stack_ptr as usize], &self.memory);
+        self.smc_mut().clear();
+        self.history.clear();
+        self.timeline.clear();
+    }
+
+    /// Sets the emulated machine to a saved state, leaving the debugging tools alone
+    fn restore(&mut self, state: &SaveState) {
+        self.registers = state.registers;
+        let size = state.memory.len().min(self.memory.len());
+        self.memory[..size].copy_from_slice(&state.memory[..size]);
+        self.index = state.index;
+        self.pc = state.pc;
+        self.stack = state.stack;
+        self.stack_ptr = state.stack_ptr;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        for (row, &bits) in self.screen.state.iter_mut().zip(&state.screen) {
+            unpack_row(bits, row);
+        }
+        self.cycles = state.cycles;
+        self.frame_cycles = state.frame_cycles;
+        self.vblank_wait = state.vblank_wait;
+        self.seed = state.seed;
+        self.rng = state.rng.clone();
+        self.platform = state.platform;
+        self.quirks = state.quirks;
+        self.tickrate = state.tickrate;
+    }
+
+    pub(crate) fn quick_save(&self) -> Result<PathBuf, SaveStateError> {
+        let hash = self.rom_hash.as_ref().ok_or(SaveStateError::NoRom)?;
+        let path = slot_path(hash, self.state_slot);
+        self.save_state().save(&path)?;
+        Ok(path)
+    }
+
+    pub(crate) fn quick_load(&mut self) -> Result<(), SaveStateError> {
+        let hash = self.rom_hash.as_ref().ok_or(SaveStateError::NoRom)?;
+        let state = SaveState::load(&slot_path(hash, self.state_slot))?;
+        self.load_state(&state);
         Ok(())
     }
 
+    /// Switches to a platform's quirk preset and default tickrate
+    pub(crate) fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.quirks = platform.quirks();
+        self.tickrate = platform.default_tickrate();
+    }
+
     pub(crate) fn process_input(&mut self) {
         let pressed_keys = get_keys_down();
         self.keypad[1] = pressed_keys.contains(&Key1);
@@ -134,12 +410,28 @@ impl Chip8 {
         self.keypad[11] = pressed_keys.contains(&C);
         self.keypad[15] = pressed_keys.contains(&V);
 
-        if is_key_pressed(L) && self.cycle_advance {
-            log::info!("Advancing one cycle forward...");
-            self.block_cycle = false;
+        if is_key_pressed(P) {
+            self.speed.toggle_pause();
+        }
+        if is_key_pressed(N) {
+            self.speed.request(Advance::Frame);
         }
-        if is_key_down(K) && self.cycle_advance {
-            self.block_cycle = false;
+        if is_key_pressed(L) || (is_key_down(K) && self.speed.is_paused()) {
+            self.speed.request(Advance::Cycle);
+        }
+        self.speed.turbo = is_key_down(Tab);
+
+        if is_key_pressed(F5) {
+            match self.quick_save() {
+                Ok(path) => log::info!("Saved state to {}", path.display()),
+                Err(e) => log::error!("Failed to save state: {e}"),
+            }
+        }
+        if is_key_pressed(F9) {
+            match self.quick_load() {
+                Ok(_) => log::info!("Loaded state from slot {}", self.state_slot),
+                Err(e) => log::error!("Failed to load state: {e}"),
+            }
         }
 
     }
@@ -219,18 +511,27 @@ impl Chip8 {
                 let vy: usize = digit3 as usize;
 
                 self.registers[vx] |= self.registers[vy];
+                if self.quirks.logic {
+                    self.registers[0xF] = 0;
+                }
             }
             (8, _, _, 2) => {
                 let vx: usize = digit2 as usize;
                 let vy: usize = digit3 as usize;
 
                 self.registers[vx] &= self.registers[vy];
+                if self.quirks.logic {
+                    self.registers[0xF] = 0;
+                }
             }
             (8, _, _, 3) => { // 8XY3
                 let vx: usize = digit2 as usize;
                 let vy: usize = digit3 as usize;
 
                 self.registers[vx] ^= self.registers[vy];
+                if self.quirks.logic {
+                    self.registers[0xF] = 0;
+                }
             }
             (8, _, _, 4) => { // 8XY4
                 let vx: usize = digit2 as usize;
@@ -255,7 +556,7 @@ impl Chip8 {
                 let vy: usize = digit3 as usize;
 
                 let mut input = self.registers[vy];
-                if self.shift_quirk {
+                if self.quirks.shift {
                     input = self.registers[vx];
                 }
                 let lsb = input & 1;
@@ -277,7 +578,7 @@ impl Chip8 {
                 let vy: usize = digit3 as usize;
 
                 let mut input = self.registers[vy];
-                if self.shift_quirk {
+                if self.quirks.shift {
                     input = self.registers[vx];
                 }
                 let msb = (input >> 7) & 1;
@@ -302,29 +603,35 @@ impl Chip8 {
             }
             (0xB, _, _, _) => {
                 let nnn = self.opcode & 0xFFF;
+                let offset = if self.quirks.jump { self.registers[digit2 as usize] } else { self.registers[0] };
 
-                self.pc = self.registers[0] as u16 + nnn;
+                self.pc = offset as u16 + nnn;
             }
             (0xC, _, _, _) => {
                 let vx: usize = digit2 as usize;
                 let byte = self.opcode & 0x00FF;
 
-                self.registers[vx] = random::<u8>() & byte as u8;
+                self.registers[vx] = self.rng.next_byte() & byte as u8;
             }
             (0xD, _, _, _) => {
-                let x = self.registers[digit2 as usize] as u16;
-                let y = self.registers[digit3 as usize] as u16;
+                // The starting position always wraps, the sprite itself is clipped unless the wrap quirk is on
+                let x = self.registers[digit2 as usize] as u16 % SCREEN_WIDTH as u16;
+                let y = self.registers[digit3 as usize] as u16 % SCREEN_HEIGHT as u16;
                 let n = digit4;
 
                 let mut flipped = false;
 
                 for row in 0..n {
-                    let spr_byte = self.memory[self.index as usize + row as usize];
+                    let spr_byte = self.read_memory((self.index as usize + row as usize) % self.memory.len());
 
                     for col in 0..8 {
                         if (spr_byte & (0b1000_0000 >> col)) != 0 {
-                            let x = (x + col) as usize % SCREEN_WIDTH as usize;
-                            let y = (y + row) as usize % SCREEN_HEIGHT as usize;
+                            let (x, y) = ((x + col) as usize, (y + row) as usize);
+                            if !self.quirks.wrap && (x >= SCREEN_WIDTH as usize || y >= SCREEN_HEIGHT as usize) {
+                                continue;
+                            }
+                            let x = x % SCREEN_WIDTH as usize;
+                            let y = y % SCREEN_HEIGHT as usize;
 
                             if self.screen.state[y][x] {
                                 flipped = true;
@@ -339,6 +646,10 @@ impl Chip8 {
                 } else {
                     self.registers[0xF] = 0;
                 }
+
+                if self.quirks.vblank {
+                    self.vblank_wait = true;
+                }
             }
             (0xE, _, 9, 0xE) => {
                 let vx: usize = digit2 as usize;
@@ -427,15 +738,18 @@ impl Chip8 {
                 let hundreds = (value / 100.0).floor() as u8;
                 let tens = ((value / 10.0) % 10.0).floor() as u8;
                 let ones = (value % 10.0) as u8;
-                self.memory[self.index as usize] = hundreds;
-                self.memory[(self.index + 1) as usize] = tens;
-                self.memory[(self.index + 2) as usize] = ones;
+                let i = self.index as usize;
+                self.write_memory(i, hundreds);
+                self.write_memory(i + 1, tens);
+                self.write_memory(i + 2, ones);
             }
             (0xF, _, 5, 5) => {
-                let x = digit2 as usize; let i = self.index as usize; for idx in 0..=x { self.memory[i + idx] = self.registers[idx]; }
+                let x = digit2 as usize; let i = self.index as usize; for idx in 0..=x { self.write_memory(i + idx, self.registers[idx]); }
+                self.increment_index_after_memory_op(x);
             }
             (0xF, _, 6, 5) => {
-                let x = digit2 as usize; let i = self.index as usize; for idx in 0..=x { self.registers[idx] = self.memory[i + idx]; }
+                let x = digit2 as usize; let i = self.index as usize; for idx in 0..=x { self.registers[idx] = self.read_memory(i + idx); }
+                self.increment_index_after_memory_op(x);
             }
             (_, _, _, _) => {
                 return Err(Chip8Error::UnimplementedOpcode(self.opcode))
@@ -446,18 +760,391 @@ impl Chip8 {
         Ok(())
     }
 
-    pub(crate) fn cycle(&mut self) -> Chip8Result<()> {
-        if self.pc >= 0xFFF {
-            self.pc = 0x200;
+    pub(crate) fn watchpoints(&self) -> Vec<(u16, Watch)> {
+        self.memory.observer::<Watchpoints>()
+            .map(|watchpoints| watchpoints.addresses.iter().map(|(&address, &watch)| (address, watch)).collect())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_watchpoint(&mut self, address: u16, watch: Watch) {
+        if self.memory.observer::<Watchpoints>().is_none() {
+            self.memory.add_observer(Box::<Watchpoints>::default());
+        }
+        if let Some(watchpoints) = self.memory.observer_mut::<Watchpoints>() {
+            watchpoints.addresses.insert(address, watch);
         }
+    }
 
-        let hi_byte = self.memory[self.pc as usize] as u16;
-        let lo_byte = self.memory[(self.pc + 1) as usize] as u16;
-        self.opcode = (hi_byte << 8) | lo_byte;
+    /// Takes the watchpoints off the bus once none are left, so that accesses aren't checked against them
+    pub(crate) fn remove_watchpoint(&mut self, address: u16) {
+        if let Some(watchpoints) = self.memory.observer_mut::<Watchpoints>() {
+            watchpoints.addresses.remove(&address);
+            if watchpoints.addresses.is_empty() {
+                self.memory.remove_observer::<Watchpoints>();
+            }
+        }
+    }
 
-        self.pc += 2;
+    pub(crate) fn coverage(&self) -> &Coverage {
+        self.coverage.get(&self.memory)
+    }
+
+    pub(crate) fn coverage_mut(&mut self) -> &mut Coverage {
+        self.coverage.get_mut(&mut self.memory)
+    }
+
+    pub(crate) fn coverage_enabled(&self) -> bool {
+        self.coverage.is_enabled()
+    }
+
+    pub(crate) fn set_coverage_enabled(&mut self, enabled: bool) {
+        self.coverage.set_enabled(&mut self.memory, enabled);
+    }
+
+    pub(crate) fn smc(&self) -> &SmcDetector {
+        self.smc.get(&self.memory)
+    }
+
+    pub(crate) fn smc_mut(&mut self) -> &mut SmcDetector {
+        self.smc.get_mut(&mut self.memory)
+    }
+
+    pub(crate) fn smc_enabled(&self) -> bool {
+        self.smc.is_enabled()
+    }
+
+    pub(crate) fn set_smc_enabled(&mut self, enabled: bool) {
+        self.smc.set_enabled(&mut self.memory, enabled);
+    }
+
+    pub(crate) fn access_map(&self) -> &AccessMap {
+        self.access_map.get(&self.memory)
+    }
+
+    pub(crate) fn access_map_mut(&mut self) -> &mut AccessMap {
+        self.access_map.get_mut(&mut self.memory)
+    }
+
+    pub(crate) fn access_map_enabled(&self) -> bool {
+        self.access_map.is_enabled()
+    }
+
+    pub(crate) fn set_access_map_enabled(&mut self, enabled: bool) {
+        self.access_map.set_enabled(&mut self.memory, enabled);
+    }
+
+    /// Addresses written during the last step or frames run
+    pub(crate) fn recent_writes(&self) -> &RecentWrites {
+        self.recent_writes.get(&self.memory)
+    }
+
+    pub(crate) fn recent_writes_enabled(&self) -> bool {
+        self.recent_writes.is_enabled()
+    }
+
+    pub(crate) fn set_recent_writes_enabled(&mut self, enabled: bool) {
+        self.recent_writes.set_enabled(&mut self.memory, enabled);
+        self.recent_writes.get_mut(&mut self.memory).clear();
+    }
+
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.memory.observer_mut::<Watchpoints>().and_then(Watchpoints::take_hit)
+    }
+
+    /// Reads memory as data
+    fn read_memory(&mut self, address: usize) -> u8 {
+        self.memory.read(address as u16)
+    }
+
+    fn write_memory(&mut self, address: usize, value: u8) {
+        self.memory.write(address as u16, value);
+    }
+
+    fn increment_index_after_memory_op(&mut self, x: usize) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let increment = if self.quirks.memory_increment_by_x { x } else { x + 1 };
+        self.index = self.index.wrapping_add(increment as u16);
+    }
+
+    /// Runs one host frame's worth of emulation at the current speed
+    pub(crate) fn run(&mut self) -> Chip8Result<()> {
+        let advance = self.speed.take_advance();
+        let frames = if advance.is_none() { self.speed.frames_due() } else { 0 };
+        if advance.is_some() || frames > 0 {
+            // Events during single steps don't need to break later on
+            self.smc_mut().take_break();
+            self.take_watch_hit();
+            self.recent_writes.get_mut(&mut self.memory).clear();
+        }
+
+        match advance {
+            Some(Advance::Cycle) => {
+                self.step()?;
+            }
+            Some(Advance::Frame) => self.run_frame()?,
+            None => {
+                for _ in 0..frames {
+                    if self.run_frame_until_breakpoint()? {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `run_frame`, but pauses at breakpoints and watchpoints, or on self-modifying code if enabled. Returns true if it did.
+    fn run_frame_until_breakpoint(&mut self) -> Chip8Result<bool> {
+        loop {
+            let frame_done = self.step()?;
+            if self.breakpoints.contains(&self.pc) {
+                log::info!("Breakpoint at {:03X}", self.pc);
+                self.speed.state = RunState::Paused;
+                return Ok(true);
+            }
+            if let Some(hit) = self.take_watch_hit() {
+                log::info!("Watchpoint: {hit}");
+                self.speed.state = RunState::Paused;
+                return Ok(true);
+            }
+            if self.smc_mut().take_break() {
+                log::info!("Self-modifying code, paused at {:03X}", self.pc);
+                self.speed.state = RunState::Paused;
+                return Ok(true);
+            }
+            if frame_done {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Runs one frame: `tickrate` instructions followed by a timer tick
+    pub(crate) fn run_frame(&mut self) -> Chip8Result<()> {
+        while !self.step()? {}
+        Ok(())
+    }
+
+    /// Executes one instruction. Returns true when it completed a frame and the timers ticked
+    pub(crate) fn step(&mut self) -> Chip8Result<bool> {
+        if self.movie.before_step(self.cycles, &mut self.keypad) {
+            self.finish_playback();
+        }
+
+        if self.timeline.enabled && self.timeline.before_step(self.cycles, keypad_to_mask(&self.keypad)) {
+            let snapshot = self.save_state();
+            self.timeline.add_snapshot(snapshot);
+        }
+
+        let result = self.cycle();
+        self.frame_cycles += 1;
+        self.cycles += 1;
+
+        let frame_done = self.frame_cycles >= self.tickrate || self.vblank_wait;
+        if frame_done {
+            self.tick_timers();
+            self.memory.end_frame();
+            // At frame boundaries inside the emulation, so the timeline and movies replay them too
+            self.cheats.apply(&mut self.memory);
+        }
+        result.map(|_| frame_done)
+    }
+
+    /// Saves the differences between the unpatched ROM and memory up to `end` as a patch, IPS or BPS
+    /// depending on the extension. Without `end`, memory up to the last byte changed past the ROM is included.
+    pub(crate) fn create_patch(&self, path: &Path, end: Option<usize>) -> Result<(), PatchError> {
+        if self.rom_file.is_none() {
+            return Err(PatchError::NoRom);
+        }
+        let end = end.unwrap_or_else(|| self.modified_end()).clamp(START_ADDRESS, self.memory.len());
+        let target = &self.memory[START_ADDRESS..end];
+        fs::write(path, patch::create(&self.rom_original, target, path))?;
+        Ok(())
+    }
+
+    /// Address after the last byte of program memory that differs from the unpatched ROM, which counts
+    /// as zeros past its end. Never before the end of the loaded ROM.
+    fn modified_end(&self) -> usize {
+        let original = |address: usize| self.rom_original.get(address - START_ADDRESS).copied().unwrap_or(0);
+        (START_ADDRESS..self.memory.len())
+            .rev()
+            .find(|&address| self.memory[address] != original(address))
+            .map_or(0, |address| address + 1)
+            .max(START_ADDRESS + self.rom.len())
+    }
+
+    /// Goes to the state before instruction `cycle` of the timeline, by re-executing from the snapshot before it
+    pub(crate) fn seek(&mut self, cycle: u64) -> Chip8Result<()> {
+        if !self.movie.is_idle() {
+            log::warn!("Can't travel in time while a movie is recording or playing");
+            return Ok(());
+        }
+        let cycle = cycle.clamp(self.timeline.start(), self.timeline.end());
+        let Some(snapshot) = self.timeline.snapshot_before(cycle).cloned() else {
+            return Ok(());
+        };
+
+        self.speed.state = RunState::Paused;
+        self.restore(&snapshot);
+        self.replay(cycle, |_, _| {})
+    }
+
+    /// Undoes the last instruction
+    pub(crate) fn step_back(&mut self) -> Chip8Result<()> {
+        if self.cycles > self.timeline.start() {
+            self.seek(self.cycles - 1)?;
+        }
+        Ok(())
+    }
+
+    /// Goes back to the last time PC was at a breakpoint or an instruction hit a watchpoint,
+    /// searching one snapshot interval at a time
+    pub(crate) fn reverse_continue(&mut self) -> Chip8Result<()> {
+        if !self.movie.is_idle() {
+            log::warn!("Can't travel in time while a movie is recording or playing");
+            return Ok(());
+        }
+        let current = self.cycles;
+        let mut end = current;
+        while let Some(snapshot) = end.checked_sub(1).and_then(|last| self.timeline.snapshot_before(last)).cloned() {
+            self.restore(&snapshot);
+            let mut found = Some(self.cycles).filter(|_| self.breakpoints.contains(&self.pc));
+            self.replay(end, |chip8, watch_hit| {
+                if chip8.cycles < end && (watch_hit || chip8.breakpoints.contains(&chip8.pc)) {
+                    found = Some(chip8.cycles);
+                }
+            })?;
+            if let Some(cycle) = found {
+                log::info!("Reverse continue stopped at {cycle}");
+                return self.seek(cycle);
+            }
+            end = snapshot.cycles;
+        }
+
+        log::info!("No earlier breakpoint or watchpoint hit");
+        self.seek(current)
+    }
+
+    /// Re-executes recorded input until instruction `cycle`, calling `visit` after every instruction with
+    /// whether it hit a watchpoint. Tools that accumulate over a run are off meanwhile.
+    fn replay(&mut self, cycle: u64, mut visit: impl FnMut(&Chip8, bool)) -> Chip8Result<()> {
+        let enabled = [
+            self.profiler.enabled,
+            self.coverage_enabled(),
+            self.smc_enabled(),
+            self.access_map_enabled(),
+            self.timeline.enabled,
+        ];
+        self.profiler.enabled = false;
+        self.set_coverage_enabled(false);
+        self.set_smc_enabled(false);
+        self.set_access_map_enabled(false);
+        self.timeline.enabled = false;
+        self.history.truncate(self.cycles);
+        self.take_watch_hit();
+
+        let mut result = Ok(());
+        while self.cycles < cycle && result.is_ok() {
+            self.keypad = mask_to_keypad(self.timeline.keypad_at(self.cycles));
+            result = self.step().map(|_| ());
+            let watch_hit = self.take_watch_hit().is_some();
+            visit(self, watch_hit);
+        }
+
+        let [profiler, coverage, smc, access_map, timeline] = enabled;
+        self.profiler.enabled = profiler;
+        self.set_coverage_enabled(coverage);
+        self.set_smc_enabled(smc);
+        self.set_access_map_enabled(access_map);
+        self.timeline.enabled = timeline;
+        self.profiler.sync_stack(&self.stack[..self.stack_ptr as usize], &self.memory);
+        result
+    }
+
+    /// Restarts the ROM and starts recording a movie
+    pub(crate) fn start_recording(&mut self) {
+        self.restart();
+        self.movie = MovieState::Recording(Movie {
+            rom_hash: sha1_hex(&self.rom),
+            seed: self.seed,
+            rng_mode: self.rng.mode(),
+            platform: self.platform,
+            quirks: self.quirks,
+            tickrate: self.tickrate,
+            inputs: Vec::new(),
+            length: 0,
+            final_state_hash: String::new(),
+        });
+    }
+
+    pub(crate) fn stop_recording(&mut self) -> Option<Movie> {
+        let MovieState::Recording(mut movie) = std::mem::take(&mut self.movie) else {
+            return None;
+        };
+        movie.length = self.cycles;
+        movie.final_state_hash = self.state_hash();
+        Some(movie)
+    }
+
+    /// Restarts the ROM with the movie's settings and plays it back in real time
+    pub(crate) fn play_movie(&mut self, movie: Movie) {
+        if movie.rom_hash != sha1_hex(&self.rom) {
+            log::warn!("Movie was recorded with a different ROM ({})", movie.rom_hash);
+        }
+
+        self.set_rng(movie.rng_mode, movie.seed);
+        self.platform = movie.platform;
+        self.quirks = movie.quirks;
+        self.tickrate = movie.tickrate;
+        self.movie_verified = None;
+        self.restart();
+        self.movie = MovieState::Playing { movie, next: 0 };
+    }
+
+    /// Plays a movie back as fast as possible and checks that it ends in the recorded state
+    pub(crate) fn verify_movie(&mut self, movie: Movie) -> bool {
+        self.play_movie(movie);
+        while !self.movie.is_idle() {
+            // Errors don't stop the recording either, the movie carries on after them
+            if let Err(e) = self.step() {
+                log::error!("{e}");
+            }
+        }
+        self.movie_verified == Some(true)
+    }
+
+    fn finish_playback(&mut self) {
+        let MovieState::Playing { movie, .. } = std::mem::take(&mut self.movie) else {
+            return;
+        };
 
-        self.execute()?;
+        let verified = self.state_hash() == movie.final_state_hash;
+        if verified {
+            log::info!("Movie finished, final state matches the recording");
+        } else {
+            log::warn!("Movie finished, final state differs from the recording");
+        }
+        self.movie_verified = Some(verified);
+    }
+
+    /// SHA-1 of everything that affects emulation, for checking that two runs ended up in the same state
+    pub(crate) fn state_hash(&self) -> String {
+        let mut state = Vec::with_capacity(self.memory.len() + 128);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.index.to_be_bytes());
+        state.extend_from_slice(&self.pc.to_be_bytes());
+        state.extend(self.stack.iter().flat_map(|address| address.to_be_bytes()));
+        state.extend_from_slice(&[self.stack_ptr, self.delay_timer, self.sound_timer]);
+        state.extend(self.screen.state.iter().flatten().map(|&pixel| pixel as u8));
+        state.extend(self.rng.state_bytes());
+        sha1_hex(&state)
+    }
+
+    fn tick_timers(&mut self) {
+        self.frame_cycles = 0;
+        self.vblank_wait = false;
 
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
@@ -465,10 +1152,55 @@ impl Chip8 {
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
         }
-        Ok(())
+        self.rng.tick();
+    }
+
+    pub(crate) fn cycle(&mut self) -> Chip8Result<()> {
+        if self.pc >= 0xFFF {
+            self.pc = 0x200;
+        }
+
+        self.opcode = self.memory.fetch(self.pc);
+        if self.profiler.enabled {
+            self.profiler.record(self.pc, self.opcode);
+        }
+
+        let pc = self.pc;
+        self.pc += 2;
+
+        if !self.history.enabled {
+            return self.execute();
+        }
+        let before = self.registers_snapshot();
+        let result = self.execute();
+        self.history.record(HistoryEntry {
+            cycle: self.cycles,
+            pc,
+            opcode: self.opcode,
+            before,
+            after: self.registers_snapshot(),
+        });
+        result
+    }
+
+    fn registers_snapshot(&self) -> Registers {
+        Registers { v: self.registers, index: self.index }
+    }
+}
+
+#[cfg(test)]
+impl Chip8 {
+    /// Machine with `rom` loaded and started, for tests
+    pub(crate) fn with_rom(rom: &[u8]) -> Self {
+        let mut chip8 = Self { rom: rom.to_vec(), ..Self::default() };
+        chip8.restart();
+        chip8
     }
 }
+
 #[cfg(test)]
+// Tests set up the machine a field at a time
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
 
@@ -514,4 +1246,99 @@ mod tests {
         chip8.execute().unwrap();
         assert_eq!(chip8.registers[0xD], 50);
     }
+
+    #[test]
+    fn timers_tick_once_per_frame() {
+        let mut chip8 = Chip8::default();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0x12, 0x00]); // JP 200
+        chip8.tickrate = 10;
+        chip8.delay_timer = 5;
+
+        for _ in 0..9 {
+            assert!(!chip8.step().unwrap());
+        }
+        assert_eq!(chip8.delay_timer, 5);
+        assert!(chip8.step().unwrap());
+        assert_eq!(chip8.delay_timer, 4);
+    }
+
+    #[test]
+    fn patch_from_modified_memory_round_trips() {
+        let directory = std::env::temp_dir().join(format!("chipmate-patch-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let rom_file = directory.join("rom.ch8");
+        fs::write(&rom_file, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+
+        let mut chip8 = Chip8::default();
+        chip8.load_rom_with_patch(&rom_file, None).unwrap();
+        chip8.memory[0x201] = 0xEE;
+        chip8.memory[0x210] = 0x42;
+        for extension in ["bps", "ips"] {
+            let patch = directory.join(format!("patch.{extension}"));
+            chip8.create_patch(&patch, None).unwrap();
+
+            let mut patched = Chip8::default();
+            patched.load_rom_with_patch(&rom_file, Some(&patch)).unwrap();
+            assert_eq!(patched.rom, chip8.memory[0x200..0x211]);
+
+            // An explicit end leaves out the byte past the ROM
+            chip8.create_patch(&patch, Some(0x204)).unwrap();
+            patched.load_rom_with_patch(&rom_file, Some(&patch)).unwrap();
+            assert_eq!(patched.rom, [0x00, 0xEE, 0x12, 0x00]);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn recent_writes_are_those_of_the_last_step() {
+        let mut chip8 = Chip8::default();
+        // LD I, 300; LD [I], V0; LD I, 302; LD [I], V1
+        chip8.memory[0x200..0x208].copy_from_slice(&[0xA3, 0x00, 0xF0, 0x55, 0xA3, 0x02, 0xF1, 0x55]);
+        chip8.set_recent_writes_enabled(true);
+
+        for written in [&[][..], &[0x300], &[], &[0x302, 0x303]] {
+            chip8.speed.request(Advance::Cycle);
+            chip8.run().unwrap();
+            assert!((0x300..0x304).all(|address| chip8.recent_writes().contains(address) == written.contains(&address)));
+        }
+        // Nothing runs while paused, so the last step's writes stay highlighted
+        chip8.run().unwrap();
+        assert!(chip8.recent_writes().contains(0x303));
+    }
+
+    #[test]
+    fn op_dxyn_wraps_start_and_clips_sprite() {
+        let mut chip8 = Chip8::default();
+        chip8.opcode = 0xD011;
+        chip8.index = FONTSET_START_ADDRESS as u16; // 0xF0, four pixels wide
+        chip8.quirks.wrap = false;
+
+        // The start position wraps, so x 66 draws at 2
+        chip8.registers[0] = 66;
+        chip8.execute().unwrap();
+        assert!(chip8.screen.state[0][2..6].iter().all(|&pixel| pixel));
+
+        // Pixels past the right edge are clipped
+        chip8.screen.reset();
+        chip8.registers[0] = 62;
+        chip8.execute().unwrap();
+        assert!(chip8.screen.state[0][62] && chip8.screen.state[0][63]);
+        assert!(!chip8.screen.state[0][0] && !chip8.screen.state[0][1]);
+
+        // ...unless the wrap quirk is on
+        chip8.screen.reset();
+        chip8.quirks.wrap = true;
+        chip8.execute().unwrap();
+        assert!(chip8.screen.state[0][0] && chip8.screen.state[0][1]);
+    }
+
+    #[test]
+    fn op_dxyn_ends_frame_with_vblank_quirk() {
+        let mut chip8 = Chip8::default();
+        chip8.memory[0x200..0x202].copy_from_slice(&[0xD0, 0x11]); // DRW V0, V1, 1
+        chip8.tickrate = 10;
+        chip8.quirks.vblank = true;
+
+        assert!(chip8.step().unwrap());
+    }
 }
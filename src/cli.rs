@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use clap::Parser;
+use log::LevelFilter;
+use macroquad::window::Conf;
+use crate::chip8::Chip8;
+use crate::palette::Palette;
+use crate::quirks::Platform;
+use crate::screen::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::speed::{PlaybackSpeed, RunState};
+
+/// Room left next to the screen for the side panel when the window size comes from `--scale`
+const SIDE_PANEL_WIDTH: i32 = 320;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A CHIP-8 emulator")]
+pub(crate) struct Args {
+    /// ROM to load on start
+    pub(crate) rom: Option<PathBuf>,
+    /// Platform preset for quirks and tickrate, as named by the CHIP-8 database (e.g. originalChip8, superchip, xochip)
+    #[arg(long, value_parser = parse_platform)]
+    pub(crate) platform: Option<Platform>,
+    /// Instructions executed per frame
+    #[arg(long)]
+    pub(crate) tickrate: Option<u32>,
+    /// Seed for CXNN's random number generator, in hex
+    #[arg(long, value_parser = parse_seed)]
+    pub(crate) seed: Option<u64>,
+    /// Playback speed: 0.25, 0.5 or 1
+    #[arg(long, value_parser = parse_speed)]
+    pub(crate) speed: Option<PlaybackSpeed>,
+    /// Name of a built-in palette or path to an Octo palette file
+    #[arg(long)]
+    pub(crate) palette: Option<String>,
+    /// Size of a CHIP-8 pixel in the initial window
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub(crate) scale: Option<u32>,
+    /// Start in fullscreen
+    #[arg(long)]
+    pub(crate) fullscreen: bool,
+    /// Start with emulation paused
+    #[arg(long)]
+    pub(crate) paused: bool,
+    /// Script to run, one command per line: wait FRAMES, press KEY, release KEY, screenshot, save SLOT, load SLOT, pause, resume, quit
+    #[arg(long)]
+    pub(crate) script: Option<PathBuf>,
+    /// Log level: off, error, warn, info, debug or trace
+    #[arg(long, default_value = "debug")]
+    pub(crate) log_level: LevelFilter,
+}
+
+/// Command-line arguments, parsed on first use
+pub(crate) fn args() -> &'static Args {
+    static ARGS: OnceLock<Args> = OnceLock::new();
+    ARGS.get_or_init(Args::parse)
+}
+
+pub(crate) fn window_conf() -> Conf {
+    let args = args();
+    let mut conf = Conf {
+        window_title: "chipmate".to_owned(),
+        fullscreen: args.fullscreen,
+        ..Conf::default()
+    };
+    if let Some(scale) = args.scale {
+        conf.window_width = SCREEN_WIDTH as i32 * scale as i32 + SIDE_PANEL_WIDTH;
+        conf.window_height = SCREEN_HEIGHT as i32 * scale as i32;
+    }
+    conf
+}
+
+impl Args {
+    /// Applies the settings given on the command line. Called after the ROM is loaded, so they
+    /// take precedence over its database profile.
+    pub(crate) fn apply(&self, chip8: &mut Chip8) {
+        if let Some(platform) = self.platform {
+            chip8.set_platform(platform);
+        }
+        if let Some(tickrate) = self.tickrate {
+            chip8.tickrate = tickrate.max(1);
+        }
+        if let Some(seed) = self.seed {
+            chip8.set_rng(chip8.rng_mode(), seed);
+        }
+        if let Some(speed) = self.speed {
+            chip8.speed.playback = speed;
+        }
+        if self.paused {
+            chip8.speed.state = RunState::Paused;
+        }
+        if let Some(ref palette) = self.palette {
+            match find_palette(palette) {
+                Some(palette) => chip8.screen.palette = palette,
+                None => log::error!("Unknown palette {palette}"),
+            }
+        }
+    }
+}
+
+fn find_palette(name: &str) -> Option<Palette> {
+    let builtin = Palette::builtins().into_iter().find(|palette| palette.name.eq_ignore_ascii_case(name));
+    builtin.or_else(|| match Palette::load(Path::new(name)) {
+        Ok(palette) => Some(palette),
+        Err(e) => {
+            log::error!("{e}");
+            None
+        }
+    })
+}
+
+fn parse_platform(id: &str) -> Result<Platform, String> {
+    Platform::from_id(id).ok_or_else(|| {
+        let ids: Vec<_> = Platform::ALL.iter().map(Platform::id).collect();
+        format!("expected one of {}", ids.join(", "))
+    })
+}
+
+fn parse_seed(seed: &str) -> Result<u64, String> {
+    u64::from_str_radix(seed.trim_start_matches("0x"), 16).map_err(|_| "expected up to 16 hex digits".to_owned())
+}
+
+fn parse_speed(speed: &str) -> Result<PlaybackSpeed, String> {
+    let factor: f32 = speed.parse().map_err(|_| "expected a number".to_owned())?;
+    PlaybackSpeed::ALL.into_iter()
+        .find(|playback| playback.factor() == factor)
+        .ok_or_else(|| "expected 0.25, 0.5 or 1".to_owned())
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use thiserror::Error;
+use crate::bus::{Access, BusObserver};
+
+pub(crate) const EXECUTED: u8 = 1;
+/// Read as data by DXYN or FX65
+pub(crate) const READ: u8 = 2;
+/// Written by FX55 or FX33
+pub(crate) const WRITTEN: u8 = 4;
+
+#[derive(Error, Debug)]
+pub(crate) enum CoverageError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid symbol file, line {0}")]
+    Parse(usize),
+}
+
+/// How every byte of memory has been accessed since coverage was last cleared
+pub(crate) struct Coverage {
+    flags: Vec<u8>,
+}
+
+/// Source locations of instructions, one per line: `<hex address> <source file> <line>`
+#[derive(Debug, Default)]
+pub(crate) struct Symbols {
+    lines: BTreeMap<u16, (String, u32)>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self {
+            flags: vec![0; 0x1000],
+        }
+    }
+}
+
+impl Coverage {
+    fn mark(&mut self, address: usize, access: u8) {
+        self.flags[address] |= access;
+    }
+
+    pub(crate) fn get(&self, address: usize) -> u8 {
+        self.flags[address]
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.flags.fill(0);
+    }
+
+    /// Number of bytes in `range` with each kind of access
+    pub(crate) fn count(&self, range: Range<usize>, access: u8) -> usize {
+        self.flags[range].iter().filter(|&&flags| flags & access != 0).count()
+    }
+
+    /// lcov tracefile of the executed instructions. Without symbols, the program is a single
+    /// source file named `rom_name` whose line numbers are instruction addresses.
+    pub(crate) fn to_lcov(&self, rom_name: &str, program: Range<usize>, symbols: Option<&Symbols>) -> String {
+        let executed = |address: usize| (self.flags[address] | self.flags[(address + 1) % self.flags.len()]) & EXECUTED != 0;
+
+        let mut files: BTreeMap<&str, BTreeMap<u32, bool>> = BTreeMap::new();
+        match symbols {
+            Some(symbols) => {
+                for (&address, (file, line)) in &symbols.lines {
+                    let hit = files.entry(file).or_default().entry(*line).or_default();
+                    *hit |= executed(address as usize % self.flags.len());
+                }
+            }
+            None => {
+                let lines = files.entry(rom_name).or_default();
+                for address in program.step_by(2) {
+                    lines.insert(address as u32, executed(address));
+                }
+            }
+        }
+
+        let mut lcov = String::new();
+        for (file, lines) in files {
+            let _ = writeln!(lcov, "TN:\nSF:{file}");
+            for (line, hit) in &lines {
+                let _ = writeln!(lcov, "DA:{line},{}", *hit as u8);
+            }
+            let hit = lines.values().filter(|&&hit| hit).count();
+            let _ = writeln!(lcov, "LF:{}\nLH:{hit}\nend_of_record", lines.len());
+        }
+        lcov
+    }
+}
+
+impl BusObserver for Coverage {
+    fn access(&mut self, address: u16, access: Access, _value: u8, _pc: u16) {
+        let flag = match access {
+            Access::Execute => EXECUTED,
+            Access::Read => READ,
+            Access::Write => WRITTEN,
+        };
+        self.mark(address as usize, flag);
+    }
+}
+
+impl Symbols {
+    pub(crate) fn load(path: &Path) -> Result<Symbols, CoverageError> {
+        Symbols::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(source: &str) -> Result<Symbols, CoverageError> {
+        let mut lines = BTreeMap::new();
+        for (number, line) in source.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let mut fields = line.split_whitespace();
+            let parsed = (|| {
+                let address = u16::from_str_radix(fields.next()?.trim_start_matches("0x"), 16).ok()?;
+                let file = fields.next()?.to_owned();
+                let line = fields.next()?.parse().ok()?;
+                Some((address, (file, line)))
+            })();
+            let (address, location) = parsed.ok_or(CoverageError::Parse(number + 1))?;
+            lines.insert(address, location);
+        }
+        Ok(Symbols { lines })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lcov_by_address_and_by_source_line() {
+        let mut coverage = Coverage::default();
+        coverage.mark(0x200, EXECUTED);
+        coverage.mark(0x201, EXECUTED);
+        coverage.mark(0x204, READ);
+
+        let lcov = coverage.to_lcov("game.ch8", 0x200..0x206, None);
+        assert_eq!(lcov, "TN:\nSF:game.ch8\nDA:512,1\nDA:514,0\nDA:516,0\nLF:3\nLH:1\nend_of_record\n");
+
+        let symbols = Symbols::parse("200 game.8o 3\n0x202 game.8o 3\n204 game.8o 4\n").unwrap();
+        let lcov = coverage.to_lcov("game.ch8", 0x200..0x206, Some(&symbols));
+        assert_eq!(lcov, "TN:\nSF:game.8o\nDA:3,1\nDA:4,0\nLF:2\nLH:1\nend_of_record\n");
+
+        assert!(matches!(Symbols::parse("200 game.8o\n"), Err(CoverageError::Parse(1))));
+    }
+}
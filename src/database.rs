@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use macroquad::color::Color;
+use serde::Deserialize;
+use crate::palette::parse_hex_color;
+use crate::quirks::{Platform, Quirks};
+
+/// `programs.json` from the community CHIP-8 database (https://github.com/chip-8/chip-8-database)
+const BUNDLED_DATABASE: &str = include_str!("../assets/database/programs.json");
+/// A newer `programs.json` placed next to the executable replaces the bundled one
+const DATABASE_FILE: &str = "programs.json";
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// Settings the database recommends for a single ROM
+#[derive(Clone, Debug)]
+pub(crate) struct RomProfile {
+    pub(crate) title: String,
+    pub(crate) platform: Platform,
+    pub(crate) quirks: Quirks,
+    pub(crate) tickrate: u32,
+    /// Background colour first, then the colours for each bit plane combination
+    pub(crate) colors: Vec<Color>,
+    /// Key hints, e.g. "up" -> 5
+    pub(crate) keys: BTreeMap<String, u8>,
+}
+
+pub(crate) struct Database {
+    profiles: HashMap<String, RomProfile>,
+}
+
+impl Database {
+    /// Returns the database, parsing it on first use. Empty if it's invalid.
+    pub(crate) fn get() -> &'static Database {
+        static DATABASE: OnceLock<Database> = OnceLock::new();
+        DATABASE.get_or_init(|| {
+            let json = override_path()
+                .and_then(|path| fs::read_to_string(&path).ok().inspect(|_| {
+                    log::info!("Using the ROM database at {}", path.display());
+                }))
+                .unwrap_or_else(|| BUNDLED_DATABASE.to_owned());
+            Database::parse(&json).unwrap_or_else(|e| {
+                log::error!("Failed to parse ROM database: {e}");
+                Database { profiles: HashMap::new() }
+            })
+        })
+    }
+
+    pub(crate) fn parse(json: &str) -> serde_json::Result<Database> {
+        let programs: Vec<Program> = serde_json::from_str(json)?;
+        let mut profiles = HashMap::new();
+
+        for program in programs {
+            for (hash, rom) in program.roms {
+                // The first platform we know of among those the ROM runs on
+                let platform = rom.platforms.iter()
+                    .find_map(|id| Platform::from_id(id))
+                    .unwrap_or_default();
+
+                let mut quirks = platform.quirks();
+                if let Some(overrides) = rom.quirky_platforms.get(platform.id()) {
+                    overrides.apply(&mut quirks);
+                }
+
+                let colors = rom.colors
+                    .map(|colors| colors.pixels.iter().filter_map(|hex| parse_hex_color(hex)).collect())
+                    .unwrap_or_default();
+
+                profiles.insert(hash.to_lowercase(), RomProfile {
+                    title: program.title.clone(),
+                    platform,
+                    quirks,
+                    tickrate: rom.tickrate.unwrap_or(platform.default_tickrate()),
+                    colors,
+                    keys: rom.keys,
+                });
+            }
+        }
+
+        Ok(Database { profiles })
+    }
+
+    /// Looks up a ROM by its lowercase hex SHA-1 hash
+    pub(crate) fn lookup(&self, sha1: &str) -> Option<&RomProfile> {
+        self.profiles.get(sha1)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.profiles.len()
+    }
+}
+
+impl QuirkOverrides {
+    fn apply(&self, quirks: &mut Quirks) {
+        let fields = [
+            (self.shift, &mut quirks.shift),
+            (self.memory_increment_by_x, &mut quirks.memory_increment_by_x),
+            (self.memory_leave_i_unchanged, &mut quirks.memory_leave_i_unchanged),
+            (self.wrap, &mut quirks.wrap),
+            (self.jump, &mut quirks.jump),
+            (self.vblank, &mut quirks.vblank),
+            (self.logic, &mut quirks.logic),
+        ];
+        for (value, quirk) in fields {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+}
+
+/// `programs.json` next to the executable, so it's found however chipmate is started
+fn override_path() -> Option<PathBuf> {
+    Some(std::env::current_exe().ok()?.with_file_name(DATABASE_FILE))
+}
+
+/// Hex digest used as the database key
+pub(crate) fn sha1_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r##"[{
+        "title": "Sample",
+        "roms": {
+            "ABCDEF": {
+                "platforms": ["superchip", "xochip"],
+                "quirkyPlatforms": { "superchip": { "shift": false } },
+                "tickrate": 20,
+                "colors": { "pixels": ["#000000", "#ff8800"] },
+                "keys": { "up": 5 }
+            }
+        }
+    }]"##;
+
+    #[test]
+    fn applies_quirky_platform_overrides() {
+        let database = Database::parse(SAMPLE).unwrap();
+        let profile = database.lookup("abcdef").unwrap();
+
+        assert_eq!(profile.platform, Platform::SuperChip);
+        assert!(!profile.quirks.shift);
+        assert!(profile.quirks.jump);
+        assert_eq!(profile.tickrate, 20);
+        assert_eq!(profile.colors.len(), 2);
+        assert_eq!(profile.keys["up"], 5);
+    }
+
+    #[test]
+    fn bundled_database_parses() {
+        assert!(Database::parse(BUNDLED_DATABASE).is_ok());
+    }
+
+    #[test]
+    fn sha1_of_empty_rom() {
+        assert_eq!(sha1_hex(&[]), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+}
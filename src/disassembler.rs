@@ -0,0 +1,79 @@
+/// Mnemonic for an instruction, in the syntax of Cowgod's CHIP-8 reference.
+/// SUPER-CHIP and XO-CHIP instructions are named too, even though they aren't emulated.
+pub(crate) fn disassemble(opcode: u16) -> String {
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+    let n = opcode & 0xF;
+    let nn = opcode & 0xFF;
+    let nnn = opcode & 0xFFF;
+
+    match (opcode >> 12, x, y, n) {
+        (0, 0, 0xE, 0) => "CLS".to_owned(),
+        (0, 0, 0xE, 0xE) => "RET".to_owned(),
+        (0, 0, 0xC, _) => format!("SCD {n}"),
+        (0, 0, 0xD, _) => format!("SCU {n}"),
+        (0, 0, 0xF, 0xB) => "SCR".to_owned(),
+        (0, 0, 0xF, 0xC) => "SCL".to_owned(),
+        (0, 0, 0xF, 0xD) => "EXIT".to_owned(),
+        (0, 0, 0xF, 0xE) => "LOW".to_owned(),
+        (0, 0, 0xF, 0xF) => "HIGH".to_owned(),
+        (0, _, _, _) => format!("SYS {nnn:03X}"),
+        (1, _, _, _) => format!("JP {nnn:03X}"),
+        (2, _, _, _) => format!("CALL {nnn:03X}"),
+        (3, _, _, _) => format!("SE V{x:X}, {nn:02X}"),
+        (4, _, _, _) => format!("SNE V{x:X}, {nn:02X}"),
+        (5, _, _, 0) => format!("SE V{x:X}, V{y:X}"),
+        (5, _, _, 2) => format!("SAVE V{x:X}-V{y:X}"),
+        (5, _, _, 3) => format!("LOAD V{x:X}-V{y:X}"),
+        (6, _, _, _) => format!("LD V{x:X}, {nn:02X}"),
+        (7, _, _, _) => format!("ADD V{x:X}, {nn:02X}"),
+        (8, _, _, 0) => format!("LD V{x:X}, V{y:X}"),
+        (8, _, _, 1) => format!("OR V{x:X}, V{y:X}"),
+        (8, _, _, 2) => format!("AND V{x:X}, V{y:X}"),
+        (8, _, _, 3) => format!("XOR V{x:X}, V{y:X}"),
+        (8, _, _, 4) => format!("ADD V{x:X}, V{y:X}"),
+        (8, _, _, 5) => format!("SUB V{x:X}, V{y:X}"),
+        (8, _, _, 6) => format!("SHR V{x:X}, V{y:X}"),
+        (8, _, _, 7) => format!("SUBN V{x:X}, V{y:X}"),
+        (8, _, _, 0xE) => format!("SHL V{x:X}, V{y:X}"),
+        (9, _, _, 0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xA, _, _, _) => format!("LD I, {nnn:03X}"),
+        (0xB, _, _, _) => format!("JP V0, {nnn:03X}"),
+        (0xC, _, _, _) => format!("RND V{x:X}, {nn:02X}"),
+        (0xD, _, _, _) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        (0xE, _, 9, 0xE) => format!("SKP V{x:X}"),
+        (0xE, _, 0xA, 1) => format!("SKNP V{x:X}"),
+        (0xF, 0, 0, 0) => "LD I, long".to_owned(),
+        (0xF, _, 0, 1) => format!("PLANE {x}"),
+        (0xF, 0, 0, 2) => "AUDIO".to_owned(),
+        (0xF, _, 0, 7) => format!("LD V{x:X}, DT"),
+        (0xF, _, 0, 0xA) => format!("LD V{x:X}, K"),
+        (0xF, _, 1, 5) => format!("LD DT, V{x:X}"),
+        (0xF, _, 1, 8) => format!("LD ST, V{x:X}"),
+        (0xF, _, 1, 0xE) => format!("ADD I, V{x:X}"),
+        (0xF, _, 2, 9) => format!("LD F, V{x:X}"),
+        (0xF, _, 3, 0) => format!("LD HF, V{x:X}"),
+        (0xF, _, 3, 3) => format!("LD B, V{x:X}"),
+        (0xF, _, 3, 0xA) => format!("PITCH V{x:X}"),
+        (0xF, _, 5, 5) => format!("LD [I], V{x:X}"),
+        (0xF, _, 6, 5) => format!("LD V{x:X}, [I]"),
+        (0xF, _, 7, 5) => format!("LD R, V{x:X}"),
+        (0xF, _, 8, 5) => format!("LD V{x:X}, R"),
+        _ => format!("DW {opcode:04X}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x22A4), "CALL 2A4");
+        assert_eq!(disassemble(0x8AB4), "ADD VA, VB");
+        assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xF265), "LD V2, [I]");
+        assert_eq!(disassemble(0xFFFF), "DW FFFF");
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeSet;
+use egui_macroquad::egui::{self, Color32, RichText};
+use crate::chip8::{Chip8, START_ADDRESS};
+use crate::disassembler::disassemble;
+use crate::memory_view::PC_COLOR;
+
+/// Instructions involved in self-modifying code, which the static listing can't be trusted for
+const MODIFIED_COLOR: Color32 = Color32::from_rgb(200, 120, 0);
+
+/// Disassembly of the program and of every other address executed so far.
+/// Clicking the gutter toggles a breakpoint.
+pub(crate) struct DisassemblyView {
+    pub(crate) follow_pc: bool,
+    scroll_to: Option<usize>,
+    last_pc: u16,
+}
+
+impl Default for DisassemblyView {
+    fn default() -> Self {
+        Self {
+            follow_pc: true,
+            scroll_to: None,
+            last_pc: 0,
+        }
+    }
+}
+
+impl DisassemblyView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("Disassembly").open(open).show(ctx, |ui| {
+            ui.checkbox(&mut self.follow_pc, "Follow PC");
+
+            let smc = chip8.smc();
+            let mut addresses = listing(chip8, |address| smc.is_instruction(address));
+            if let Err(row) = addresses.binary_search(&(chip8.pc() as usize)) {
+                addresses.insert(row, chip8.pc() as usize);
+            }
+            if self.follow_pc && chip8.pc() != self.last_pc {
+                self.jump(chip8.pc() as usize);
+            }
+            self.last_pc = chip8.pc();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            let mut scroll_area = egui::ScrollArea::vertical().max_height(400.0);
+            if let Some(address) = self.scroll_to.take() {
+                let row = addresses.partition_point(|&listed| listed < address);
+                // Keep a few instructions of context above the target
+                let offset = (row_height + ui.spacing().item_spacing.y) * row.saturating_sub(4) as f32;
+                scroll_area = scroll_area.vertical_scroll_offset(offset);
+            }
+
+            scroll_area.show_rows(ui, row_height, addresses.len(), |ui, range| {
+                for &address in &addresses[range] {
+                    self.row(ui, chip8, address);
+                }
+            });
+
+            if !chip8.smc().events.is_empty() {
+                ui.collapsing(format!("Self-modifying code ({})", chip8.smc().events.len()), |ui| {
+                    egui::ScrollArea::vertical().id_source("smc").max_height(120.0).show(ui, |ui| {
+                        for event in &chip8.smc().events {
+                            if ui.link(event.to_string()).clicked() {
+                                self.jump(event.address as usize);
+                            }
+                        }
+                    });
+                });
+            }
+        });
+    }
+
+    /// Scrolls to an address on the next frame
+    pub(crate) fn jump(&mut self, address: usize) {
+        self.scroll_to = Some(address);
+    }
+
+    fn row(&self, ui: &mut egui::Ui, chip8: &mut Chip8, address: usize) {
+        let opcode = u16::from_be_bytes([chip8.memory[address], chip8.memory[(address + 1) % chip8.memory.len()]]);
+        let breakpoint = chip8.breakpoints.contains(&(address as u16));
+
+        ui.horizontal(|ui| {
+            let gutter = RichText::new(if breakpoint { "●" } else { " " }).monospace().color(Color32::RED);
+            if ui.add(egui::Label::new(gutter).sense(egui::Sense::click())).clicked() {
+                if breakpoint {
+                    chip8.breakpoints.remove(&(address as u16));
+                } else {
+                    chip8.breakpoints.insert(address as u16);
+                }
+            }
+
+            let mut text = RichText::new(format!("{address:03X}  {opcode:04X}  {}", disassemble(opcode))).monospace();
+            if address == chip8.pc() as usize {
+                text = text.background_color(PC_COLOR);
+            } else if chip8.smc().is_marked(address) {
+                text = text.color(MODIFIED_COLOR);
+            }
+            let label = ui.label(text);
+            if chip8.smc().is_marked(address) {
+                label.on_hover_text("Modified by the program, may differ from what runs");
+            }
+        });
+    }
+}
+
+/// Program addresses two bytes apart, plus every address `executed` returns true for, in order
+pub(crate) fn listing(chip8: &Chip8, executed: impl Fn(usize) -> bool) -> Vec<usize> {
+    let mut addresses: BTreeSet<usize> = (START_ADDRESS..START_ADDRESS + chip8.rom.len()).step_by(2).collect();
+    addresses.extend((0..chip8.memory.len()).filter(|&address| executed(address)));
+    addresses.into_iter().collect()
+}
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use egui_macroquad::egui::{self, RichText};
+use crate::chip8::Chip8;
+use crate::disassembler::disassemble;
+
+/// Registers as they were before and after an instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Registers {
+    pub(crate) v: [u8; 16],
+    pub(crate) index: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HistoryEntry {
+    pub(crate) cycle: u64,
+    pub(crate) pc: u16,
+    pub(crate) opcode: u16,
+    pub(crate) before: Registers,
+    pub(crate) after: Registers,
+}
+
+impl HistoryEntry {
+    /// Registers the instruction changed, e.g. `V3 05→06  I 300→302`
+    pub(crate) fn deltas(&self) -> String {
+        let mut deltas: Vec<String> = (0..16)
+            .filter(|&x| self.before.v[x] != self.after.v[x])
+            .map(|x| format!("V{x:X} {:02X}→{:02X}", self.before.v[x], self.after.v[x]))
+            .collect();
+        if self.before.index != self.after.index {
+            deltas.push(format!("I {:03X}→{:03X}", self.before.index, self.after.index));
+        }
+        deltas.join("  ")
+    }
+}
+
+/// The last `capacity` executed instructions, oldest first
+pub(crate) struct History {
+    pub(crate) enabled: bool,
+    pub(crate) capacity: usize,
+    entries: VecDeque<HistoryEntry>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 1024,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl History {
+    pub(crate) fn record(&mut self, entry: HistoryEntry) {
+        while self.entries.len() >= self.capacity.max(1) {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Forgets instructions from `cycle` on, when going back in time
+    pub(crate) fn truncate(&mut self, cycle: u64) {
+        while self.entries.back().is_some_and(|entry| entry.cycle >= cycle) {
+            self.entries.pop_back();
+        }
+    }
+
+    pub(crate) fn entries(&self) -> &VecDeque<HistoryEntry> {
+        &self.entries
+    }
+}
+
+/// Scrollable list of the history. Follows the newest entry unless scrolled up.
+#[derive(Default)]
+pub(crate) struct HistoryView {
+    selected: Option<u64>,
+}
+
+impl HistoryView {
+    /// Returns the address of an entry when it's clicked
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) -> Option<u16> {
+        let mut clicked = None;
+        egui::Window::new("History").open(open).show(ctx, |ui| {
+            let history = &mut chip8.history;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut history.enabled, "Enabled");
+                ui.add(egui::DragValue::new(&mut history.capacity).clamp_range(1..=1 << 20));
+                ui.label("Instructions");
+                if ui.button("Clear").clicked() {
+                    history.clear();
+                }
+            });
+
+            let entries = history.entries();
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical().max_height(400.0).stick_to_bottom(true).show_rows(ui, row_height, entries.len(), |ui, range| {
+                for entry in entries.range(range) {
+                    let text = format!(
+                        "{:>10}  {:03X}  {:04X}  {:<18}{}",
+                        entry.cycle, entry.pc, entry.opcode, disassemble(entry.opcode), entry.deltas(),
+                    );
+                    let selected = self.selected == Some(entry.cycle);
+                    if ui.selectable_label(selected, RichText::new(text).monospace()).clicked() {
+                        self.selected = Some(entry.cycle);
+                        clicked = Some(entry.pc);
+                    }
+                }
+            });
+        });
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_last_entries_with_their_deltas() {
+        let mut history = History { capacity: 2, ..History::default() };
+        for cycle in 0..3 {
+            let before = Registers { v: [cycle as u8; 16], index: 0x300 };
+            let mut after = before;
+            after.v[3] += 1;
+            history.record(HistoryEntry { cycle, pc: 0x200 + 2 * cycle as u16, opcode: 0x7301, before, after });
+        }
+
+        let cycles: Vec<u64> = history.entries().iter().map(|entry| entry.cycle).collect();
+        assert_eq!(cycles, [1, 2]);
+        assert_eq!(history.entries()[1].deltas(), "V3 02→03");
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::analyzer::analyze;
+use crate::database::{sha1_hex, Database};
+use crate::quirks::Platform;
+use crate::savestate::{slot_path, SaveState};
+
+const LIBRARY_FILE: &str = "library.json";
+/// Extensions of CHIP-8, SUPER-CHIP and XO-CHIP ROMs
+pub(crate) const ROM_EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+const SOURCE_EXTENSION: &str = "8o";
+const MAX_RECENT: usize = 10;
+/// Quick save slots searched for thumbnails
+const SLOTS: u8 = 10;
+
+#[derive(Error, Debug)]
+pub(crate) enum LibraryError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid library file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The parts of the library that are remembered between sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct LibrarySettings {
+    pub(crate) directories: Vec<PathBuf>,
+    /// SHA-1 hashes of favourite ROMs
+    pub(crate) favourites: BTreeSet<String>,
+    /// Most recently played first
+    pub(crate) recent: Vec<PathBuf>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct LibraryEntry {
+    pub(crate) path: PathBuf,
+    /// Database title, or the file name for unknown ROMs
+    pub(crate) title: String,
+    /// None for Octo sources, which can't be analyzed before they're assembled
+    pub(crate) platform: Option<Platform>,
+    pub(crate) size: usize,
+    pub(crate) hash: String,
+    pub(crate) in_database: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Library {
+    pub(crate) settings: LibrarySettings,
+    pub(crate) entries: Vec<LibraryEntry>,
+    /// The directories are only scanned once the library is shown
+    scanned: bool,
+}
+
+impl LibrarySettings {
+    fn load(path: &Path) -> Result<LibrarySettings, LibraryError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), LibraryError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl LibraryEntry {
+    fn read(path: &Path) -> std::io::Result<LibraryEntry> {
+        let data = fs::read(path)?;
+        let hash = sha1_hex(&data);
+        let is_source = has_extension(path, &[SOURCE_EXTENSION]);
+        let profile = Database::get().lookup(&hash);
+
+        let title = profile.map_or_else(
+            || path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+            |profile| profile.title.clone(),
+        );
+        let platform = match profile {
+            Some(profile) => Some(profile.platform),
+            None if is_source => None,
+            None => Some(analyze(&data).platform),
+        };
+
+        Ok(LibraryEntry {
+            path: path.to_path_buf(),
+            title,
+            platform,
+            size: data.len(),
+            hash,
+            in_database: profile.is_some(),
+        })
+    }
+
+    pub(crate) fn is_source(&self) -> bool {
+        has_extension(&self.path, &[SOURCE_EXTENSION])
+    }
+
+    /// File to load for this entry. Octo sources load the ROM assembled next to them, if there is one.
+    pub(crate) fn rom_path(&self) -> Option<PathBuf> {
+        if !self.is_source() {
+            return Some(self.path.clone());
+        }
+        ROM_EXTENSIONS.iter()
+            .map(|extension| self.path.with_extension(extension))
+            .find(|path| path.is_file())
+    }
+
+    pub(crate) fn matches(&self, search: &str) -> bool {
+        let search = search.to_lowercase();
+        self.title.to_lowercase().contains(&search)
+            || self.path.to_string_lossy().to_lowercase().contains(&search)
+            || self.hash.starts_with(&search)
+    }
+}
+
+impl Library {
+    /// Loads the library settings. The directories are scanned on first use.
+    pub(crate) fn open() -> Library {
+        let settings = match LibrarySettings::load(Path::new(LIBRARY_FILE)) {
+            Ok(settings) => settings,
+            Err(LibraryError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => LibrarySettings::default(),
+            Err(e) => {
+                log::error!("Failed to load ROM library: {e}");
+                LibrarySettings::default()
+            }
+        };
+
+        Library { settings, entries: Vec::new(), scanned: false }
+    }
+
+    pub(crate) fn save(&self) {
+        if let Err(e) = self.settings.save(Path::new(LIBRARY_FILE)) {
+            log::error!("Failed to save ROM library: {e}");
+        }
+    }
+
+    pub(crate) fn ensure_scanned(&mut self) {
+        if !self.scanned {
+            self.scan();
+        }
+    }
+
+    pub(crate) fn scan(&mut self) {
+        self.scanned = true;
+        self.entries.clear();
+        for directory in &self.settings.directories {
+            if let Err(e) = scan_directory(directory, &mut self.entries) {
+                log::error!("Failed to scan {}: {e}", directory.display());
+            }
+        }
+        self.entries.sort_by_key(|entry| entry.title.to_lowercase());
+        log::info!("Found {} ROMs in the library", self.entries.len());
+    }
+
+    pub(crate) fn is_favourite(&self, hash: &str) -> bool {
+        self.settings.favourites.contains(hash)
+    }
+
+    pub(crate) fn toggle_favourite(&mut self, hash: &str) {
+        if !self.settings.favourites.remove(hash) {
+            self.settings.favourites.insert(hash.to_owned());
+        }
+        self.save();
+    }
+
+    pub(crate) fn add_recent(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.settings.recent.retain(|recent| *recent != path);
+        self.settings.recent.insert(0, path);
+        self.settings.recent.truncate(MAX_RECENT);
+        self.save();
+    }
+}
+
+/// The most recent quick save of a ROM, used as its thumbnail
+pub(crate) fn latest_save_state(hash: &str) -> Option<SaveState> {
+    let modified = |path: &PathBuf| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+
+    let path = (0..SLOTS)
+        .map(|slot| slot_path(hash, slot))
+        .filter_map(|path| Some((modified(&path)?, path)))
+        .max_by_key(|&(time, _): &(SystemTime, _)| time)?
+        .1;
+    SaveState::load(&path).ok()
+}
+
+fn scan_directory(directory: &Path, entries: &mut Vec<LibraryEntry>) -> std::io::Result<()> {
+    for dir_entry in fs::read_dir(directory)? {
+        let (path, file_type) = match dir_entry.and_then(|dir_entry| Ok((dir_entry.path(), dir_entry.file_type()?))) {
+            Ok(entry) => entry,
+            Err(e) => {
+                log::warn!("Skipping an entry of {}: {e}", directory.display());
+                continue;
+            }
+        };
+        // Symlinked directories aren't followed, so links can't make the scan loop forever
+        if file_type.is_dir() {
+            if let Err(e) = scan_directory(&path, entries) {
+                log::warn!("Skipping {}: {e}", path.display());
+            }
+        } else if has_extension(&path, &ROM_EXTENSIONS) || has_extension(&path, &[SOURCE_EXTENSION]) {
+            match LibraryEntry::read(&path) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => log::warn!("Skipping {}: {e}", path.display()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.iter().any(|candidate| candidate.eq_ignore_ascii_case(extension)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scans_roms_and_sources() {
+        let directory = std::env::temp_dir().join(format!("chipmate-library-{}", std::process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("nested/game.CH8"), [0x12, 0x00]).unwrap();
+        fs::write(directory.join("game.8o"), ": main jump main").unwrap();
+        fs::write(directory.join("readme.txt"), "not a ROM").unwrap();
+
+        let mut entries = Vec::new();
+        scan_directory(&directory, &mut entries).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        entries.sort_by(|a, b| a.title.cmp(&b.title));
+
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_source() && entries[0].platform.is_none());
+        assert_eq!(entries[1].platform, Some(Platform::OriginalChip8));
+        assert_eq!(entries[1].size, 2);
+        assert!(entries[1].matches("GAME.ch"));
+    }
+}
@@ -1,21 +1,64 @@
+mod access_map;
+mod analyzer;
+mod bus;
+mod capture;
+mod cheats;
 mod chip8;
+mod cli;
+mod coverage;
+mod database;
+mod disassembler;
+mod disassembly_view;
+mod history;
+mod library;
+mod memory_view;
+mod movie;
+mod palette;
+mod patch;
+mod profiler;
+mod quirks;
+mod reload;
+mod rng;
+mod savestate;
 mod screen;
+mod script;
+mod shader;
+mod smc;
+mod speed;
+mod sprite_view;
+mod state_diff;
+mod timeline;
 mod ui;
 
-use log::LevelFilter;
 use macroquad::prelude::*;
 use crate::chip8::Chip8;
+use crate::cli::{args, window_conf};
+use crate::script::Script;
 use crate::ui::Ui;
 
-#[macroquad::main("chipmate")]
+#[macroquad::main(window_conf)]
 async fn main() {
+    let args = args();
     env_logger::builder()
-        .filter_level(LevelFilter::Debug)
+        .filter_level(args.log_level)
         .init();
 
     let mut chip8 = Chip8::default();
     let mut ui = Ui::default();
 
+    if let Some(ref path) = args.rom {
+        match chip8.load_rom(path) {
+            Ok(_) => ui.library.add_recent(path),
+            Err(e) => log::error!("{e}"),
+        }
+    }
+    args.apply(&mut chip8);
+
+    let mut script = args.script.as_deref().map(Script::load).transpose().unwrap_or_else(|e| {
+        log::error!("Failed to load script: {e}");
+        None
+    });
+
     loop {
         clear_background(BLACK);
 
@@ -26,7 +69,7 @@ async fn main() {
                 chip8.reset(); // Reset emulator state to prepare for loading
 
                 match chip8.load_rom(path) { // Load new ROM
-                    Ok(_) => {}
+                    Ok(_) => ui.library.add_recent(path),
                     Err(e) => {
                         log::error!("{e}");
                     }
@@ -35,28 +78,27 @@ async fn main() {
             }
         }
 
+        ui.hot_reload.update(&mut chip8);
+
         chip8.process_input();
 
+        if let Some(ref mut script) = script {
+            if !script.update(&mut chip8, &ui.capture) {
+                break;
+            }
+        }
 
-        if (chip8.cycle_advance && !chip8.block_cycle) 
-            || (!chip8.cycle_advance && chip8.block_cycle) {
-            match chip8.cycle() {
-                Ok(_) => {}
-                Err(e) => {
-                    log::error!("{e}");
-                }
+        match chip8.run() {
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("{e}");
             }
-            chip8.block_cycle = true;
         }
 
+        ui.capture.update(&chip8.screen);
+
         // Calculate UI
-        ui.render(&mut chip8.memory,
-                  &mut chip8.registers,
-                  &mut chip8.screen.show_grid,
-                  &mut chip8.cycle_advance,
-                  &mut chip8.shift_quirk,
-                  chip8.opcode
-        );
+        ui.render(&mut chip8);
 
         // Update emulator screen
         chip8.screen.update(&ui.rect);
@@ -0,0 +1,235 @@
+use egui_macroquad::egui::{self, Color32, RichText};
+use crate::chip8::{Chip8, FONTSET_END_ADDRESS, FONTSET_START_ADDRESS, START_ADDRESS};
+use crate::coverage::{EXECUTED, READ, WRITTEN};
+
+const BYTES_PER_ROW: usize = 16;
+
+const FONT_COLOR: Color32 = Color32::from_rgb(40, 60, 110);
+const PROGRAM_COLOR: Color32 = Color32::from_rgb(30, 80, 40);
+const STACK_COLOR: Color32 = Color32::from_rgb(100, 40, 110);
+const INDEX_COLOR: Color32 = Color32::from_rgb(150, 100, 20);
+pub(crate) const PC_COLOR: Color32 = Color32::from_rgb(150, 30, 30);
+/// Text colour of bytes written during the last step or frames run
+const WRITE_COLOR: Color32 = Color32::YELLOW;
+
+/// Hex view of RAM with the font, program, I, PC and stack targets marked
+#[derive(Default)]
+pub(crate) struct MemoryView {
+    selected: Option<usize>,
+    /// Row to scroll to on the next frame
+    scroll_to: Option<usize>,
+    goto_input: String,
+    /// Hex bytes to write at the selected address
+    write_input: String,
+    search_input: String,
+    /// Addresses where the last search matched
+    results: Vec<usize>,
+    /// Index into `results` of the match shown
+    result: usize,
+    /// Colour bytes by coverage instead of by region
+    show_coverage: bool,
+}
+
+impl MemoryView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("RAM").open(open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Go to PC").clicked() {
+                    self.jump(chip8.pc() as usize);
+                }
+                if ui.button("Go to I").clicked() {
+                    self.jump(chip8.index() as usize);
+                }
+                ui.add(egui::TextEdit::singleline(&mut self.goto_input).desired_width(40.0));
+                let address = usize::from_str_radix(self.goto_input.trim(), 16).ok()
+                    .filter(|&address| address < chip8.memory.len());
+                if ui.add_enabled(address.is_some(), egui::Button::new("Go")).clicked() {
+                    self.jump(address.unwrap_or_default());
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(&mut self.search_input).desired_width(120.0))
+                    .on_hover_text("Hex bytes, ?? matches any byte, e.g. A2 ?? 60");
+                let pattern = parse_pattern(&self.search_input);
+                if ui.add_enabled(pattern.is_some(), egui::Button::new("Find")).clicked() {
+                    self.results = find_pattern(&chip8.memory, &pattern.unwrap_or_default());
+                    self.result = 0;
+                    if let Some(&address) = self.results.first() {
+                        self.jump(address);
+                    }
+                }
+                if !self.results.is_empty() {
+                    if ui.button("Next").clicked() {
+                        self.result = (self.result + 1) % self.results.len();
+                        self.jump(self.results[self.result]);
+                    }
+                    ui.label(format!("{}/{}", self.result + 1, self.results.len()));
+                }
+            });
+
+            if let Some(address) = self.selected {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{address:03X}"));
+                    ui.add(egui::DragValue::new(&mut chip8.memory[address]).hexadecimal(2, false, true));
+                    ui.add(egui::TextEdit::singleline(&mut self.write_input).desired_width(120.0))
+                        .on_hover_text("Hex bytes to write from here on, e.g. A2 2A 60");
+                    let bytes = parse_bytes(&self.write_input).filter(|bytes| address + bytes.len() <= chip8.memory.len());
+                    if ui.add_enabled(bytes.is_some(), egui::Button::new("Write")).clicked() {
+                        let bytes = bytes.unwrap_or_default();
+                        chip8.memory[address..address + bytes.len()].copy_from_slice(&bytes);
+                        self.jump(address + bytes.len() - 1);
+                        self.write_input.clear();
+                    }
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.show_coverage, "Coverage");
+                if self.show_coverage {
+                    for (access, name) in [(EXECUTED, "Executed"), (READ, "Read"), (WRITTEN, "Written")] {
+                        ui.label(RichText::new(name).background_color(coverage_color(access)));
+                    }
+                } else {
+                    for (color, name) in [
+                        (FONT_COLOR, "Font"),
+                        (PROGRAM_COLOR, "Program"),
+                        (STACK_COLOR, "Stack"),
+                        (INDEX_COLOR, "I"),
+                        (PC_COLOR, "PC"),
+                    ] {
+                        ui.label(RichText::new(name).background_color(color));
+                    }
+                }
+                let mut recent_writes = chip8.recent_writes_enabled();
+                if ui.checkbox(&mut recent_writes, RichText::new("Recent writes").color(WRITE_COLOR))
+                    .on_hover_text("Bytes written during the last step or frame")
+                    .changed()
+                {
+                    chip8.set_recent_writes_enabled(recent_writes);
+                }
+            });
+            ui.separator();
+
+            self.bytes(ui, chip8);
+        });
+    }
+
+    pub(crate) fn jump(&mut self, address: usize) {
+        self.selected = Some(address);
+        self.scroll_to = Some(address / BYTES_PER_ROW);
+    }
+
+    fn bytes(&mut self, ui: &mut egui::Ui, chip8: &Chip8) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = chip8.memory.len() / BYTES_PER_ROW;
+
+        let mut scroll_area = egui::ScrollArea::vertical().max_height(400.0);
+        if let Some(row) = self.scroll_to.take() {
+            scroll_area = scroll_area.vertical_scroll_offset((row_height + ui.spacing().item_spacing.y) * row as f32);
+        }
+
+        let (coverage, recent_writes) = (chip8.coverage(), chip8.recent_writes());
+        scroll_area.show_rows(ui, row_height, rows, |ui, row_range| {
+            for row in row_range {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = 4.0;
+                    ui.label(RichText::new(format!("{:03X}", row * BYTES_PER_ROW)).monospace().weak());
+
+                    for address in row * BYTES_PER_ROW..(row + 1) * BYTES_PER_ROW {
+                        let mut text = RichText::new(format!("{:02X}", chip8.memory[address])).monospace();
+                        let background = if self.show_coverage {
+                            Some(coverage.get(address)).filter(|&access| access != 0).map(coverage_color)
+                        } else {
+                            region_color(chip8, address)
+                        };
+                        if let Some(color) = background {
+                            text = text.background_color(color);
+                        }
+                        if recent_writes.contains(address as u16) {
+                            text = text.color(WRITE_COLOR);
+                        }
+                        if self.selected == Some(address) {
+                            text = text.underline();
+                        }
+                        if ui.add(egui::Label::new(text).sense(egui::Sense::click())).clicked() {
+                            self.selected = Some(address);
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Colour of the region an address belongs to. PC takes precedence over I, I over stack targets.
+fn region_color(chip8: &Chip8, address: usize) -> Option<Color32> {
+    let instruction_at = |start: u16| (start as usize..start as usize + 2).contains(&address);
+
+    if instruction_at(chip8.pc()) {
+        Some(PC_COLOR)
+    } else if address == chip8.index() as usize {
+        Some(INDEX_COLOR)
+    } else if chip8.stack().iter().any(|&target| instruction_at(target)) {
+        Some(STACK_COLOR)
+    } else if (START_ADDRESS..START_ADDRESS + chip8.rom.len()).contains(&address) {
+        Some(PROGRAM_COLOR)
+    } else if (FONTSET_START_ADDRESS..FONTSET_END_ADDRESS).contains(&address) {
+        Some(FONT_COLOR)
+    } else {
+        None
+    }
+}
+
+/// Executed is green, read blue and written red, mixed for bytes with several kinds of access
+fn coverage_color(access: u8) -> Color32 {
+    let channel = |flag: u8| if access & flag != 0 { 150 } else { 0 };
+    Color32::from_rgb(channel(WRITTEN), channel(EXECUTED), channel(READ))
+}
+
+/// Parses hex bytes separated by spaces or not, with ?? as a wildcard
+// `is_multiple_of` needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn parse_pattern(input: &str) -> Option<Vec<Option<u8>>> {
+    let digits: Vec<char> = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+
+    digits.chunks(2)
+        .map(|pair| match pair {
+            ['?', '?'] => Some(None),
+            _ => u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok().map(Some),
+        })
+        .collect()
+}
+
+/// Parses hex bytes like a search pattern, without wildcards
+fn parse_bytes(input: &str) -> Option<Vec<u8>> {
+    parse_pattern(input)?.into_iter().collect()
+}
+
+fn find_pattern(memory: &[u8], pattern: &[Option<u8>]) -> Vec<usize> {
+    memory.windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| window.iter().zip(pattern).all(|(&byte, expected)| expected.is_none_or(|expected| byte == expected)))
+        .map(|(address, _)| address)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_pattern_with_wildcards() {
+        let pattern = parse_pattern("a2 ??60").unwrap();
+        assert_eq!(pattern, [Some(0xA2), None, Some(0x60)]);
+        assert_eq!(find_pattern(&[0xA2, 0x01, 0x60, 0xA2, 0xFF, 0x60, 0xA2], &pattern), [0, 3]);
+
+        assert!(parse_pattern("A").is_none());
+        assert!(parse_pattern("GG").is_none());
+        assert_eq!(parse_bytes("0102"), Some(vec![1, 2]));
+        assert!(parse_bytes("01 ??").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::quirks::{Platform, Quirks};
+use crate::rng::RngMode;
+
+#[derive(Error, Debug)]
+pub(crate) enum MovieError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid movie file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// A recorded session: everything needed to replay it bit-exactly from power-on.
+/// Input is stored as keypad changes keyed by the instruction count at which they happened,
+/// so playback doesn't depend on frame timing or how the session was stepped.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Movie {
+    pub(crate) rom_hash: String,
+    pub(crate) seed: u64,
+    #[serde(default)]
+    pub(crate) rng_mode: RngMode,
+    pub(crate) platform: Platform,
+    pub(crate) quirks: Quirks,
+    pub(crate) tickrate: u32,
+    /// (instruction count, keypad bitmask) for every keypad change
+    pub(crate) inputs: Vec<(u64, u16)>,
+    /// Instructions executed while recording
+    pub(crate) length: u64,
+    /// `Chip8::state_hash` at the end of the recording
+    pub(crate) final_state_hash: String,
+}
+
+#[derive(Default)]
+pub(crate) enum MovieState {
+    #[default]
+    Idle,
+    Recording(Movie),
+    Playing {
+        movie: Movie,
+        /// Index of the next input to apply
+        next: usize,
+    },
+}
+
+impl Movie {
+    pub(crate) fn load(path: &Path) -> Result<Movie, MovieError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), MovieError> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+impl MovieState {
+    /// Called before every instruction. Records or replays the keypad.
+    /// Returns true once playback reaches the end of the movie.
+    pub(crate) fn before_step(&mut self, cycles: u64, keypad: &mut [bool; 16]) -> bool {
+        match self {
+            MovieState::Idle => false,
+            MovieState::Recording(movie) => {
+                let mask = keypad_to_mask(keypad);
+                if movie.inputs.last().map(|&(_, last)| last) != Some(mask) {
+                    movie.inputs.push((cycles, mask));
+                }
+                false
+            }
+            MovieState::Playing { movie, next } => {
+                while movie.inputs.get(*next).is_some_and(|&(at, _)| at <= cycles) {
+                    *next += 1;
+                }
+                // Reapplied every instruction, as the host keyboard overwrites the keypad every frame
+                if let Some(&(_, mask)) = next.checked_sub(1).and_then(|last| movie.inputs.get(last)) {
+                    *keypad = mask_to_keypad(mask);
+                }
+                cycles >= movie.length
+            }
+        }
+    }
+
+    pub(crate) fn is_idle(&self) -> bool {
+        matches!(self, MovieState::Idle)
+    }
+}
+
+pub(crate) fn keypad_to_mask(keypad: &[bool; 16]) -> u16 {
+    keypad.iter().enumerate().fold(0, |mask, (key, &down)| mask | ((down as u16) << key))
+}
+
+pub(crate) fn mask_to_keypad(mask: u16) -> [bool; 16] {
+    std::array::from_fn(|key| mask & (1 << key) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip8::Chip8;
+    use crate::quirks::Platform;
+
+    /// Waits for a key, then draws a random byte's worth of pixels at a random position, forever
+    const ROM: [u8; 14] = [
+        0xF0, 0x0A, // LD V0, K
+        0xC1, 0xFF, // RND V1, 0xFF
+        0xC2, 0x1F, // RND V2, 0x1F
+        0xA2, 0x0C, // LD I, 0x20C
+        0xD1, 0x21, // DRW V1, V2, 1
+        0x12, 0x00, // JP 0x200
+        0xAA, 0x55, // sprite
+    ];
+
+    fn record() -> Movie {
+        let mut chip8 = Chip8::with_rom(&ROM);
+        chip8.set_platform(Platform::ModernChip8);
+        chip8.start_recording();
+
+        for frame in 0..120 {
+            let key = (frame / 10) % 16;
+            chip8.keypad = mask_to_keypad(if frame % 3 == 0 { 1 << key } else { 0 });
+            chip8.run_frame().unwrap();
+        }
+        chip8.stop_recording().unwrap()
+    }
+
+    #[test]
+    fn replay_is_bit_exact() {
+        let movie = record();
+        assert!(movie.inputs.len() > 1);
+
+        let mut chip8 = Chip8::with_rom(&ROM);
+        assert!(chip8.verify_movie(movie.clone()));
+
+        let mut tampered = movie;
+        tampered.inputs.pop();
+        assert!(!chip8.verify_movie(tampered));
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+use macroquad::color::Color;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Display colours. Index 0 is the background, 1 and 2 are the XO-CHIP bit planes, 3 is where both planes overlap.
+/// Plain CHIP-8 only uses the first two, and only those are drawn until XO-CHIP planes are emulated.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Palette {
+    pub(crate) name: String,
+    pub(crate) colors: [Color; 4],
+    pub(crate) grid: Color,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum PaletteError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid palette file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("invalid colour '{0}'")]
+    InvalidColor(String),
+}
+
+/// On-disk format. Uses the same keys as Octo's options so its palettes can be imported directly.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PaletteFile {
+    #[serde(default)]
+    name: String,
+    background_color: String,
+    fill_color: String,
+    fill_color2: String,
+    blend_color: String,
+    #[serde(default)]
+    grid_color: Option<String>,
+}
+
+const fn rgb(hex: u32) -> Color {
+    Color::from_rgba((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255)
+}
+
+/// Name, colours and grid colour of the palettes shipped with chipmate
+const BUILTINS: [(&str, [u32; 4], u32); 5] = [
+    ("Classic", [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555], 0x00FF00),
+    ("Amber", [0x1A0E00, 0xFFB000, 0xB37B00, 0x664600], 0x4D3500),
+    ("Green phosphor", [0x001100, 0x33FF33, 0x20A020, 0x0D400D], 0x0D400D),
+    ("Octo", [0x996600, 0xFFCC00, 0xFF6600, 0x662200], 0x664400),
+    ("LCD", [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F], 0x8BAC0F),
+];
+
+impl Palette {
+    pub(crate) fn builtins() -> Vec<Palette> {
+        BUILTINS
+            .into_iter()
+            .map(|(name, colors, grid)| Palette {
+                name: name.to_owned(),
+                colors: colors.map(rgb),
+                grid: rgb(grid),
+            })
+            .collect()
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<Palette, PaletteError> {
+        let file: PaletteFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let color = |hex: &str| parse_hex_color(hex).ok_or_else(|| PaletteError::InvalidColor(hex.to_owned()));
+
+        let name = match file.name.is_empty() {
+            true => path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+            false => file.name.clone(),
+        };
+
+        Ok(Palette {
+            name,
+            colors: [
+                color(&file.background_color)?,
+                color(&file.fill_color)?,
+                color(&file.fill_color2)?,
+                color(&file.blend_color)?,
+            ],
+            grid: match file.grid_color {
+                Some(ref hex) => color(hex)?,
+                None => Palette::default().grid,
+            },
+        })
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), PaletteError> {
+        let file = PaletteFile {
+            name: self.name.clone(),
+            background_color: to_hex_color(self.colors[0]),
+            fill_color: to_hex_color(self.colors[1]),
+            fill_color2: to_hex_color(self.colors[2]),
+            blend_color: to_hex_color(self.colors[3]),
+            grid_color: Some(to_hex_color(self.grid)),
+        };
+        fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::builtins().swap_remove(0)
+    }
+}
+
+/// Parses `#RRGGBB` or `RRGGBB`
+pub(crate) fn parse_hex_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(rgb(value))
+}
+
+pub(crate) fn to_hex_color(color: Color) -> String {
+    let [r, g, b] = [color.r, color.g, color.b].map(|channel| (channel * 255.0).round() as u8);
+    format!("#{r:02X}{g:02X}{b:02X}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trip() {
+        let color = parse_hex_color("#FFCC00").unwrap();
+        assert_eq!(to_hex_color(color), "#FFCC00");
+        assert!(parse_hex_color("#FFF").is_none());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("chipmate_palette_test_{}.json", std::process::id()));
+        let palette = Palette::builtins().swap_remove(3);
+
+        palette.save(&path).unwrap();
+        let loaded = Palette::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, palette);
+    }
+}
@@ -0,0 +1,334 @@
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::bus::MEMORY_SIZE;
+use crate::chip8::START_ADDRESS;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+const BPS_HEADER: &[u8] = b"BPS1";
+/// Longest IPS record
+const IPS_MAX_RECORD: usize = 0xFFFF;
+/// Largest ROM a patch may produce, the memory after the start address
+const MAX_ROM_SIZE: usize = MEMORY_SIZE - START_ADDRESS;
+
+#[derive(Error, Debug)]
+pub(crate) enum PatchError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not an IPS or BPS patch")]
+    UnknownFormat,
+    #[error("invalid patch: {0}")]
+    Invalid(&'static str),
+    #[error("{0} checksum mismatch, the patch is for a different ROM or is damaged")]
+    Checksum(&'static str),
+    #[error("no ROM loaded")]
+    NoRom,
+}
+
+type PatchResult<T> = Result<T, PatchError>;
+
+/// A patch next to the ROM with the same name, e.g. `game.ips` or `game.bps` for `game.ch8`
+pub(crate) fn find_patch(rom: &Path) -> Option<PathBuf> {
+    ["bps", "ips"].iter().map(|extension| rom.with_extension(extension)).find(|path| path.is_file())
+}
+
+/// Applies an IPS or BPS patch, telling them apart by their header
+pub(crate) fn apply(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    if patch.starts_with(IPS_HEADER) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(BPS_HEADER) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+/// Patch turning `source` into `target`, BPS unless `path` ends in `.ips`
+pub(crate) fn create(source: &[u8], target: &[u8], path: &Path) -> Vec<u8> {
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ips")) {
+        create_ips(source, target)
+    } else {
+        create_bps(source, target)
+    }
+}
+
+/// Reads patch bytes front to back
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> PatchResult<&[u8]> {
+        let end = self.position.checked_add(count).ok_or(PatchError::Invalid("unexpected end"))?;
+        let bytes = self.data.get(self.position..end).ok_or(PatchError::Invalid("unexpected end"))?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn big_endian(&mut self, count: usize) -> PatchResult<usize> {
+        Ok(self.bytes(count)?.iter().fold(0, |value, &byte| value << 8 | byte as usize))
+    }
+
+    /// BPS variable-length number
+    fn number(&mut self) -> PatchResult<usize> {
+        let too_large = || PatchError::Invalid("number too large");
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.bytes(1)?[0] as usize;
+            value = (byte & 0x7F).checked_mul(shift).and_then(|data| value.checked_add(data)).ok_or_else(too_large)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or_else(too_large)?;
+            value = value.checked_add(shift).ok_or_else(too_large)?;
+        }
+    }
+
+    /// BPS relative offset, sign in the lowest bit
+    fn offset(&mut self) -> PatchResult<isize> {
+        let number = self.number()?;
+        let magnitude = (number >> 1) as isize;
+        Ok(if number & 1 != 0 { -magnitude } else { magnitude })
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    let mut output = rom.to_vec();
+    let mut reader = Reader { data: patch, position: IPS_HEADER.len() };
+    loop {
+        let record = reader.bytes(3)?;
+        if record == IPS_FOOTER {
+            break;
+        }
+        let offset = record.iter().fold(0, |value, &byte| value << 8 | byte as usize);
+        let (size, data) = match reader.big_endian(2)? {
+            0 => {
+                // Run-length encoded record
+                let size = reader.big_endian(2)?;
+                (size, vec![reader.bytes(1)?[0]; size])
+            }
+            size => (size, reader.bytes(size)?.to_vec()),
+        };
+        if offset + size > MAX_ROM_SIZE {
+            return Err(PatchError::Invalid("writes past the end of memory"));
+        }
+        if output.len() < offset + size {
+            output.resize(offset + size, 0);
+        }
+        output[offset..offset + size].copy_from_slice(&data);
+    }
+    // Lunar IPS extension: the size to truncate the output to
+    if let Ok(size) = reader.big_endian(3) {
+        output.truncate(size);
+    }
+    Ok(output)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    if patch.len() < BPS_HEADER.len() + 12 {
+        return Err(PatchError::Invalid("too short"));
+    }
+    let footer = patch.len() - 12;
+    let checksum = |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+    if crc32(&patch[..footer + 8]) != checksum(footer + 8) {
+        return Err(PatchError::Checksum("patch"));
+    }
+    if crc32(rom) != checksum(footer) {
+        return Err(PatchError::Checksum("source"));
+    }
+
+    let mut reader = Reader { data: &patch[..footer], position: BPS_HEADER.len() };
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(PatchError::Invalid("source size doesn't match the ROM"));
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(PatchError::Invalid("target doesn't fit in memory"));
+    }
+
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0isize, 0isize);
+    while reader.position < footer {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        if length > target_size - target.len() {
+            return Err(PatchError::Invalid("writes past the target size"));
+        }
+        match action & 3 {
+            // Source read: the bytes at the same position in the ROM
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or(PatchError::Invalid("read past the source"))?);
+            }
+            // Target read: bytes stored in the patch
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            // Source copy
+            2 => {
+                source_offset = source_offset.checked_add(reader.offset()?).ok_or(PatchError::Invalid("offset too large"))?;
+                let start = usize::try_from(source_offset).map_err(|_| PatchError::Invalid("negative offset"))?;
+                target.extend_from_slice(rom.get(start..start + length).ok_or(PatchError::Invalid("copy past the source"))?);
+                source_offset += length as isize;
+            }
+            // Target copy, byte by byte as the ranges may overlap
+            _ => {
+                target_offset = target_offset.checked_add(reader.offset()?).ok_or(PatchError::Invalid("offset too large"))?;
+                for _ in 0..length {
+                    let byte = usize::try_from(target_offset).ok()
+                        .and_then(|at| target.get(at).copied())
+                        .ok_or(PatchError::Invalid("copy past the target"))?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(PatchError::Invalid("target size doesn't match the patch"));
+    }
+    if crc32(&target) != checksum(footer + 4) {
+        return Err(PatchError::Checksum("target"));
+    }
+    Ok(target)
+}
+
+/// Runs of `target` that differ from `source`, or extend past it
+fn changed_runs(source: &[u8], target: &[u8]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, &byte) in target.iter().enumerate() {
+        if source.get(i) == Some(&byte) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((start, end)) if *end == i && i - *start < IPS_MAX_RECORD => *end += 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs
+}
+
+fn create_ips(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = IPS_HEADER.to_vec();
+    for (start, end) in changed_runs(source, target) {
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&target[start..end]);
+    }
+    patch.extend_from_slice(IPS_FOOTER);
+    if target.len() < source.len() {
+        patch.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    patch
+}
+
+/// BPS patch made of source reads for unchanged bytes and target reads for the rest
+fn create_bps(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = BPS_HEADER.to_vec();
+    for number in [source.len(), target.len(), 0] {
+        write_number(&mut patch, number);
+    }
+
+    let mut position = 0;
+    for (start, end) in changed_runs(source, target).into_iter().chain([(target.len(), target.len())]) {
+        if start > position {
+            write_number(&mut patch, (start - position - 1) << 2);
+        }
+        if end > start {
+            write_number(&mut patch, (end - start - 1) << 2 | 1);
+            patch.extend_from_slice(&target[start..end]);
+        }
+        position = end;
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+    patch
+}
+
+fn write_number(patch: &mut Vec<u8>, mut number: usize) {
+    loop {
+        let byte = (number & 0x7F) as u8;
+        number >>= 7;
+        if number == 0 {
+            patch.push(byte | 0x80);
+            return;
+        }
+        patch.push(byte);
+        number -= 1;
+    }
+}
+
+/// CRC-32 as used by zip and BPS
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ips_and_bps_round_trip() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let rom = b"\x00\xE0\xA2\x2A\x60\x0C\x61\x08".to_vec();
+        let mut target = rom.clone();
+        target[3] = 0x30;
+        target[4] = 0x70;
+        target.extend_from_slice(b"\xFF\xFF");
+
+        for path in ["fix.ips", "fix.bps"] {
+            let patch = create(&rom, &target, Path::new(path));
+            assert_eq!(apply(&rom, &patch).unwrap(), target);
+        }
+
+        // Run-length record of three 0x11 at 1, then a truncation to 5 bytes
+        let ips = b"PATCH\x00\x00\x01\x00\x00\x00\x03\x11EOF\x00\x00\x05";
+        assert_eq!(apply(&rom, ips).unwrap(), b"\x00\x11\x11\x11\x60");
+
+        let bps = create(&rom, &target, Path::new("fix.bps"));
+        assert!(matches!(apply(&target, &bps), Err(PatchError::Checksum("source"))));
+        assert!(matches!(apply(&rom, b"nonsense"), Err(PatchError::UnknownFormat)));
+    }
+
+    #[test]
+    fn rejects_malformed_patches() {
+        let rom = b"\x00\xE0\x12\x00".to_vec();
+        // BPS with valid checksums around the given numbers
+        let bps = |numbers: &[usize]| {
+            let mut patch = BPS_HEADER.to_vec();
+            for &number in numbers {
+                write_number(&mut patch, number);
+            }
+            patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+            patch.extend_from_slice(&0u32.to_le_bytes());
+            patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+            patch
+        };
+        let invalid = |patch: &[u8]| match apply(&rom, patch) {
+            Err(PatchError::Invalid(reason)) => reason,
+            result => panic!("expected an invalid patch, got {result:?}"),
+        };
+
+        let mut endless = bps(&[]);
+        endless.splice(BPS_HEADER.len()..BPS_HEADER.len(), [0; 12]);
+        let at = endless.len() - 4;
+        let crc = crc32(&endless[..at]);
+        endless[at..].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(invalid(&endless), "number too large");
+
+        assert_eq!(invalid(&bps(&[rom.len(), 0x10_0000, 0])), "target doesn't fit in memory");
+        // One byte target read (0x7F encodes as the byte FF), then a target copy far longer than the target
+        assert_eq!(invalid(&bps(&[rom.len(), 2, 0, 1, 0x7F, 0xFFFF << 2 | 3, 0])), "writes past the target size");
+        assert_eq!(invalid(b"PATCH\xFF\xFF\xFF\x00\x01\x11EOF"), "writes past the end of memory");
+        assert_eq!(invalid(b"PATCH\x00\x00\x01\x00\x05\x11"), "unexpected end");
+    }
+}
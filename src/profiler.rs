@@ -0,0 +1,289 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use egui_macroquad::egui::{self, Color32, RichText};
+use rfd::FileDialog;
+use crate::chip8::{Chip8, START_ADDRESS};
+use crate::disassembler::disassemble;
+use crate::disassembly_view::listing;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct RoutineStats {
+    pub(crate) calls: u64,
+    /// Instructions executed in the routine and everything it called
+    pub(crate) inclusive: u64,
+    /// Instructions executed in the routine itself
+    pub(crate) exclusive: u64,
+}
+
+/// A unique call stack, stored as a tree so that recording an instruction doesn't allocate
+struct StackNode {
+    parent: Option<usize>,
+    routine: u16,
+    /// Instructions executed with exactly this call stack
+    cycles: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    node: usize,
+    routine: u16,
+    /// `total` when the routine was entered
+    entry: u64,
+}
+
+/// Counts executions per address and instructions per subroutine, from 2NNN to 00EE.
+/// Fed by `Chip8::cycle` while enabled.
+pub(crate) struct Profiler {
+    pub(crate) enabled: bool,
+    hits: Vec<u64>,
+    routines: HashMap<u16, RoutineStats>,
+    nodes: Vec<StackNode>,
+    children: HashMap<(usize, u16), usize>,
+    frames: Vec<Frame>,
+    /// Instructions recorded
+    total: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        let mut profiler = Self {
+            enabled: false,
+            hits: Vec::new(),
+            routines: HashMap::new(),
+            nodes: Vec::new(),
+            children: HashMap::new(),
+            frames: Vec::new(),
+            total: 0,
+        };
+        profiler.clear();
+        profiler
+    }
+}
+
+impl Profiler {
+    pub(crate) fn clear(&mut self) {
+        self.hits = vec![0; 0x1000];
+        self.routines.clear();
+        self.nodes = vec![StackNode { parent: None, routine: START_ADDRESS as u16, cycles: 0 }];
+        self.children.clear();
+        self.frames = vec![Frame { node: 0, routine: START_ADDRESS as u16, entry: 0 }];
+        self.total = 0;
+    }
+
+    /// Called for every instruction before it's executed
+    pub(crate) fn record(&mut self, pc: u16, opcode: u16) {
+        self.total += 1;
+        self.hits[pc as usize] += 1;
+
+        let frame = self.frames[self.frames.len() - 1];
+        self.nodes[frame.node].cycles += 1;
+        self.routines.entry(frame.routine).or_default().exclusive += 1;
+
+        if opcode & 0xF000 == 0x2000 {
+            self.call(opcode & 0xFFF);
+        } else if opcode == 0x00EE && self.frames.len() > 1 {
+            let frame = self.frames.pop().unwrap_or(frame);
+            self.routines.entry(frame.routine).or_default().inclusive += self.total - frame.entry;
+        }
+    }
+
+    fn call(&mut self, routine: u16) {
+        let parent = self.frames[self.frames.len() - 1].node;
+        let next = self.nodes.len();
+        let node = *self.children.entry((parent, routine)).or_insert(next);
+        if node == next {
+            self.nodes.push(StackNode { parent: Some(parent), routine, cycles: 0 });
+        }
+
+        self.frames.push(Frame { node, routine, entry: self.total });
+        self.routines.entry(routine).or_default().calls += 1;
+    }
+
+    /// Rebuilds the call stack after a save state is loaded, from the CALL before each return address
+    pub(crate) fn sync_stack(&mut self, stack: &[u16], memory: &[u8]) {
+        self.frames.truncate(1);
+        for &return_address in stack {
+            let call = return_address.wrapping_sub(2) as usize % memory.len();
+            let opcode = u16::from_be_bytes([memory[call], memory[(call + 1) % memory.len()]]);
+            self.call(opcode & 0xFFF);
+        }
+    }
+
+    pub(crate) fn hits(&self, address: usize) -> u64 {
+        self.hits[address]
+    }
+
+    /// Stats of every routine seen, counting routines that haven't returned yet up to now
+    pub(crate) fn routines(&self) -> BTreeMap<u16, RoutineStats> {
+        let mut routines: BTreeMap<_, _> = self.routines.iter().map(|(&address, &stats)| (address, stats)).collect();
+        for frame in &self.frames {
+            routines.entry(frame.routine).or_default().inclusive += self.total - frame.entry;
+        }
+        routines
+    }
+
+    pub(crate) fn to_csv(&self) -> String {
+        let mut csv = "routine,address,calls,inclusive_cycles,exclusive_cycles\n".to_owned();
+        for (address, stats) in self.routines() {
+            let _ = writeln!(csv, "{},{address:03X},{},{},{}", routine_name(address), stats.calls, stats.inclusive, stats.exclusive);
+        }
+        csv
+    }
+
+    /// One line per call stack, in the format flamegraph.pl and inferno take
+    pub(crate) fn to_folded(&self) -> String {
+        let mut folded = String::new();
+        for (i, node) in self.nodes.iter().enumerate().filter(|(_, node)| node.cycles > 0) {
+            let mut path = vec![routine_name(node.routine)];
+            let mut parent = self.nodes[i].parent;
+            while let Some(p) = parent {
+                path.push(routine_name(self.nodes[p].routine));
+                parent = self.nodes[p].parent;
+            }
+            path.reverse();
+            let _ = writeln!(folded, "{} {}", path.join(";"), node.cycles);
+        }
+        folded
+    }
+}
+
+fn routine_name(address: u16) -> String {
+    if address as usize == START_ADDRESS {
+        "main".to_owned()
+    } else {
+        format!("sub_{address:03X}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum Column {
+    Routine,
+    Calls,
+    #[default]
+    Inclusive,
+    Exclusive,
+}
+
+/// Profiler window: routine table and a heatmap over the disassembly
+#[derive(Default)]
+pub(crate) struct ProfilerView {
+    sort: Column,
+    /// Flips the default order: descending counts, ascending addresses
+    reversed: bool,
+}
+
+impl ProfilerView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("Profiler").open(open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut chip8.profiler.enabled, "Enabled");
+                if ui.button("Clear").clicked() {
+                    chip8.profiler.clear();
+                }
+                if ui.button("Export CSV").clicked() {
+                    export(&chip8.profiler.to_csv(), "CSV", "csv");
+                }
+                if ui.button("Export folded stacks").on_hover_text("For flamegraph.pl or inferno").clicked() {
+                    export(&chip8.profiler.to_folded(), "Folded stacks", "folded");
+                }
+            });
+
+            ui.collapsing("Routines", |ui| self.routines(ui, &chip8.profiler));
+            ui.collapsing("Heatmap", |ui| heatmap(ui, chip8));
+        });
+    }
+
+    fn routines(&mut self, ui: &mut egui::Ui, profiler: &Profiler) {
+        let mut routines: Vec<_> = profiler.routines().into_iter().collect();
+        match self.sort {
+            Column::Routine => routines.sort_by_key(|&(address, _)| address),
+            Column::Calls => routines.sort_by_key(|&(_, stats)| Reverse(stats.calls)),
+            Column::Inclusive => routines.sort_by_key(|&(_, stats)| Reverse(stats.inclusive)),
+            Column::Exclusive => routines.sort_by_key(|&(_, stats)| Reverse(stats.exclusive)),
+        }
+        if self.reversed {
+            routines.reverse();
+        }
+
+        egui::ScrollArea::vertical().id_source("routines").max_height(200.0).show(ui, |ui| {
+            egui::Grid::new("routines").striped(true).show(ui, |ui| {
+                for (column, name) in [
+                    (Column::Routine, "Routine"),
+                    (Column::Calls, "Calls"),
+                    (Column::Inclusive, "Inclusive"),
+                    (Column::Exclusive, "Exclusive"),
+                ] {
+                    if ui.selectable_label(self.sort == column, name).clicked() {
+                        self.reversed = self.sort == column && !self.reversed;
+                        self.sort = column;
+                    }
+                }
+                ui.end_row();
+
+                for (address, stats) in routines {
+                    ui.label(routine_name(address));
+                    ui.label(stats.calls.to_string());
+                    ui.label(stats.inclusive.to_string());
+                    ui.label(stats.exclusive.to_string());
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+/// Disassembly of the program and every other executed address, shaded by execution count
+fn heatmap(ui: &mut egui::Ui, chip8: &Chip8) {
+    let profiler = &chip8.profiler;
+    let addresses = listing(chip8, |address| profiler.hits(address) > 0);
+
+    // Log scale, so that a hot loop doesn't leave everything else blank
+    let max = addresses.iter().map(|&address| profiler.hits(address)).max().unwrap_or(0);
+    let scale = ((max + 1) as f32).ln().max(f32::EPSILON);
+
+    let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+    egui::ScrollArea::vertical().id_source("heatmap").max_height(300.0).show_rows(ui, row_height, addresses.len(), |ui, range| {
+        for &address in &addresses[range] {
+            let hits = profiler.hits(address);
+            let opcode = u16::from_be_bytes([chip8.memory[address], chip8.memory[(address + 1) % chip8.memory.len()]]);
+            let heat = ((hits + 1) as f32).ln() / scale;
+            let text = RichText::new(format!("{address:03X}  {opcode:04X}  {:<18}{hits:>10}", disassemble(opcode)))
+                .monospace()
+                .background_color(Color32::from_rgba_unmultiplied(220, 60, 20, (heat * 200.0) as u8));
+            ui.label(text);
+        }
+    });
+}
+
+fn export(contents: &str, name: &str, extension: &str) {
+    if let Some(path) = FileDialog::new().add_filter(name, &[extension]).save_file() {
+        if let Err(e) = fs::write(&path, contents) {
+            log::error!("{e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_inclusive_and_exclusive_cycles() {
+        let mut profiler = Profiler::default();
+        profiler.record(0x200, 0x2300); // CALL 300
+        profiler.record(0x300, 0x6001);
+        profiler.record(0x302, 0x2400); // CALL 400
+        profiler.record(0x400, 0x00EE);
+        profiler.record(0x304, 0x00EE);
+        profiler.record(0x202, 0x1202);
+
+        let routines = profiler.routines();
+        assert_eq!(routines[&0x300], RoutineStats { calls: 1, inclusive: 4, exclusive: 3 });
+        assert_eq!(routines[&0x400], RoutineStats { calls: 1, inclusive: 1, exclusive: 1 });
+        assert_eq!(routines[&0x200].inclusive, 6);
+        assert_eq!(profiler.hits(0x300), 1);
+        assert_eq!(profiler.to_folded(), "main 2\nmain;sub_300 3\nmain;sub_300;sub_400 1\n");
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+
+/// chipmate's own behaviour and the interpreter families known to the CHIP-8 database.
+/// Each one comes with its own quirk preset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum Platform {
+    /// chipmate's own behaviour, used for ROMs that aren't in the database
+    #[default]
+    Chipmate,
+    OriginalChip8,
+    HybridVip,
+    ModernChip8,
+    Chip8X,
+    Chip48,
+    SuperChip1,
+    SuperChip,
+    MegaChip8,
+    XoChip,
+}
+
+/// Behaviour differences between CHIP-8 interpreters, named after the keys used by the CHIP-8 database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Quirks {
+    /// 8XY6/8XYE shift VX in place instead of shifting VY into VX
+    pub(crate) shift: bool,
+    /// FX55/FX65 increment I by X instead of X + 1
+    pub(crate) memory_increment_by_x: bool,
+    /// FX55/FX65 leave I unchanged
+    pub(crate) memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped
+    pub(crate) wrap: bool,
+    /// BNNN behaves like BXNN and jumps to XNN + VX
+    pub(crate) jump: bool,
+    /// DXYN waits for the vertical blank, so at most one sprite is drawn per frame
+    pub(crate) vblank: bool,
+    /// 8XY1/8XY2/8XY3 reset VF to zero
+    pub(crate) logic: bool,
+}
+
+impl Platform {
+    pub(crate) const ALL: [Platform; 10] = [
+        Platform::Chipmate,
+        Platform::OriginalChip8,
+        Platform::HybridVip,
+        Platform::ModernChip8,
+        Platform::Chip8X,
+        Platform::Chip48,
+        Platform::SuperChip1,
+        Platform::SuperChip,
+        Platform::MegaChip8,
+        Platform::XoChip,
+    ];
+
+    /// Identifier used by the CHIP-8 database
+    pub(crate) fn id(&self) -> &'static str {
+        match self {
+            Platform::Chipmate => "chipmate",
+            Platform::OriginalChip8 => "originalChip8",
+            Platform::HybridVip => "hybridVIP",
+            Platform::ModernChip8 => "modernChip8",
+            Platform::Chip8X => "chip8x",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip1 => "superchip1",
+            Platform::SuperChip => "superchip",
+            Platform::MegaChip8 => "megachip8",
+            Platform::XoChip => "xochip",
+        }
+    }
+
+    pub(crate) fn from_id(id: &str) -> Option<Platform> {
+        Platform::ALL.into_iter().find(|platform| platform.id() == id)
+    }
+
+    pub(crate) fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chipmate => Quirks {
+                memory_leave_i_unchanged: true,
+                wrap: true,
+                ..Quirks::default()
+            },
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Chip8X => Quirks {
+                vblank: true,
+                logic: true,
+                ..Quirks::default()
+            },
+            Platform::ModernChip8 => Quirks::default(),
+            Platform::Chip48 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..Quirks::default()
+            },
+            Platform::SuperChip1 => Quirks {
+                shift: true,
+                memory_increment_by_x: true,
+                jump: true,
+                ..Quirks::default()
+            },
+            Platform::SuperChip | Platform::MegaChip8 => Quirks {
+                shift: true,
+                memory_leave_i_unchanged: true,
+                jump: true,
+                ..Quirks::default()
+            },
+            Platform::XoChip => Quirks {
+                wrap: true,
+                ..Quirks::default()
+            },
+        }
+    }
+
+    /// Instructions executed per frame when the database doesn't specify a tickrate
+    pub(crate) fn default_tickrate(&self) -> u32 {
+        match self {
+            // One instruction and one timer tick per frame
+            Platform::Chipmate => 1,
+            Platform::OriginalChip8 | Platform::HybridVip | Platform::Chip8X => 15,
+            Platform::ModernChip8 => 12,
+            Platform::Chip48 | Platform::SuperChip1 | Platform::SuperChip => 30,
+            Platform::MegaChip8 => 1000,
+            Platform::XoChip => 100,
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Platform::Chipmate => "chipmate (classic)",
+            Platform::OriginalChip8 => "CHIP-8 (COSMAC VIP)",
+            Platform::HybridVip => "CHIP-8 (hybrid VIP)",
+            Platform::ModernChip8 => "CHIP-8 (modern)",
+            Platform::Chip8X => "CHIP-8X",
+            Platform::Chip48 => "CHIP-48",
+            Platform::SuperChip1 => "SUPER-CHIP 1.0",
+            Platform::SuperChip => "SUPER-CHIP 1.1",
+            Platform::MegaChip8 => "MEGA-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::SystemTime;
+use macroquad::time::get_time;
+use crate::chip8::Chip8;
+use crate::database::sha1_hex;
+use crate::savestate::SaveState;
+
+/// Seconds between checks of the watched files
+const POLL_INTERVAL: f64 = 0.5;
+
+/// A watched file and the modification time it had when last seen
+struct Watched {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    /// The file changed at the last poll. Acted upon once it stops changing, so that a
+    /// half-written file is never loaded.
+    pending: bool,
+}
+
+/// Reloads the ROM when it changes on disk, optionally rebuilding it from its Octo source first
+#[derive(Default)]
+pub(crate) struct HotReload {
+    pub(crate) enabled: bool,
+    pub(crate) keep_breakpoints: bool,
+    /// State to start from after a reload, with the new program copied over its memory
+    pub(crate) start_state: Option<SaveState>,
+    /// Octo source of the ROM
+    pub(crate) source: Option<PathBuf>,
+    /// Command that assembles `{source}` into `{rom}`, e.g. `octo {source} {rom}`
+    pub(crate) build_command: String,
+    rom: Option<Watched>,
+    source_watch: Option<Watched>,
+    last_poll: f64,
+    /// Build command still running. The ROM is reloaded once it has written the new file.
+    build: Option<Child>,
+}
+
+impl Watched {
+    fn new(path: &Path) -> Watched {
+        Watched {
+            path: path.to_path_buf(),
+            modified: modified(path),
+            pending: false,
+        }
+    }
+
+    /// Returns true once a change has settled
+    fn poll(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            self.pending = true;
+            false
+        } else {
+            std::mem::take(&mut self.pending)
+        }
+    }
+}
+
+impl HotReload {
+    /// Called every frame, checks the watched files every `POLL_INTERVAL` seconds
+    pub(crate) fn update(&mut self, chip8: &mut Chip8) {
+        self.poll_build();
+        if !self.enabled {
+            self.rom = None;
+            self.source_watch = None;
+            return;
+        }
+
+        let now = get_time();
+        if now - self.last_poll < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = now;
+
+        let Some(ref rom_file) = chip8.rom_file else {
+            return;
+        };
+        // A different ROM was loaded, start watching it without reloading
+        if self.rom.as_ref().map(|watched| &watched.path) != Some(rom_file) {
+            self.rom = Some(Watched::new(rom_file));
+        }
+        if self.source_watch.as_ref().map(|watched| &watched.path) != self.source.as_ref() {
+            self.source_watch = self.source.as_deref().map(Watched::new);
+        }
+
+        if self.source_watch.as_mut().is_some_and(Watched::poll) {
+            self.build(rom_file);
+        }
+        if let Some(ref mut watched) = self.rom {
+            if watched.poll() && watched.modified.is_some() {
+                let path = watched.path.clone();
+                self.reload(chip8, &path);
+            }
+        }
+    }
+
+    fn build(&mut self, rom_file: &Path) {
+        let Some(ref source) = self.source else {
+            return;
+        };
+
+        let mut args = self.build_command.split_whitespace().map(|arg| {
+            arg.replace("{source}", &source.to_string_lossy())
+                .replace("{rom}", &rom_file.to_string_lossy())
+        });
+        let Some(program) = args.next() else {
+            log::warn!("{} changed, but no build command is set", source.display());
+            return;
+        };
+
+        log::info!("{} changed, rebuilding", source.display());
+        if let Some(mut previous) = self.build.take() {
+            // The source changed again, the running build is already out of date
+            if let Err(e) = previous.kill().and_then(|_| previous.wait()) {
+                log::error!("Failed to stop build command: {e}");
+            }
+        }
+        match Command::new(program).args(args).spawn() {
+            Ok(child) => self.build = Some(child),
+            Err(e) => log::error!("Failed to run build command: {e}"),
+        }
+    }
+
+    /// Checks whether the build command has finished, without waiting for it
+    fn poll_build(&mut self) {
+        let Some(ref mut child) = self.build else {
+            return;
+        };
+        match child.try_wait() {
+            Ok(None) => return,
+            Ok(Some(status)) if status.success() => {}
+            Ok(Some(status)) => log::error!("Build command failed: {status}"),
+            Err(e) => log::error!("Failed to wait for build command: {e}"),
+        }
+        self.build = None;
+    }
+
+    fn reload(&self, chip8: &mut Chip8, path: &Path) {
+        log::info!("{} changed, reloading", path.display());
+
+        // The running program is only replaced once the new ROM has loaded
+        if let Err(e) = chip8.load_rom(path) {
+            log::error!("{e}");
+            return;
+        }
+        let breakpoints = std::mem::take(&mut chip8.breakpoints);
+        chip8.restart();
+        if self.keep_breakpoints {
+            chip8.breakpoints = breakpoints;
+        }
+
+        if let Some(ref state) = self.start_state {
+            let state = SaveState {
+                rom_hash: sha1_hex(&chip8.rom),
+                ..state.clone()
+            };
+            chip8.load_state(&state);
+            chip8.copy_rom_to_memory();
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
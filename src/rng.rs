@@ -0,0 +1,152 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+
+/// Page 0x100-0x1FF of the COSMAC VIP CHIP-8 interpreter. Its CXNN routine uses the interpreter's own
+/// code as a table of "random" bytes.
+const VIP_TABLE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+    0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+    0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+    0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+    0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+    0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+    0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+    0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+    0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+    0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+    0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+    0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+    0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+    0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum RngMode {
+    /// xorshift64*, good quality and fully determined by the seed
+    #[default]
+    Standard,
+    /// Modelled on the CXNN routine of the COSMAC VIP interpreter
+    CosmacVip,
+}
+
+/// Random number generator behind CXNN. Its whole state is serializable so that save states and
+/// movies reproduce the exact same random numbers.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum RandomSource {
+    Xorshift { state: u64 },
+    /// The two halves of the VIP's R9 register. The whole register is incremented by the 60 Hz
+    /// interrupt and by every CXNN, the high half holds the last result.
+    CosmacVip { high: u8, low: u8 },
+}
+
+impl RngMode {
+    pub(crate) const ALL: [RngMode; 2] = [RngMode::Standard, RngMode::CosmacVip];
+}
+
+impl Display for RngMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RngMode::Standard => write!(f, "Standard"),
+            RngMode::CosmacVip => write!(f, "COSMAC VIP"),
+        }
+    }
+}
+
+impl RandomSource {
+    pub(crate) fn new(mode: RngMode, seed: u64) -> RandomSource {
+        match mode {
+            RngMode::Standard => RandomSource::Xorshift { state: splitmix64(seed).max(1) },
+            RngMode::CosmacVip => RandomSource::CosmacVip { high: (seed >> 8) as u8, low: seed as u8 },
+        }
+    }
+
+    pub(crate) fn mode(&self) -> RngMode {
+        match self {
+            RandomSource::Xorshift { .. } => RngMode::Standard,
+            RandomSource::CosmacVip { .. } => RngMode::CosmacVip,
+        }
+    }
+
+    /// The whole generator state, for hashing
+    pub(crate) fn state_bytes(&self) -> Vec<u8> {
+        match *self {
+            RandomSource::Xorshift { state } => state.to_be_bytes().to_vec(),
+            RandomSource::CosmacVip { high, low } => vec![high, low],
+        }
+    }
+
+    /// Called once a frame, like the VIP's interrupt routine
+    pub(crate) fn tick(&mut self) {
+        if let RandomSource::CosmacVip { high, low } = self {
+            increment(high, low);
+        }
+    }
+
+    pub(crate) fn next_byte(&mut self) -> u8 {
+        match self {
+            RandomSource::Xorshift { state } => {
+                *state ^= *state >> 12;
+                *state ^= *state << 25;
+                *state ^= *state >> 27;
+                (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+            }
+            RandomSource::CosmacVip { high, low } => {
+                // INC R9, then VX = table[R9.0] + R9.1, shifted right through the carry and added to itself
+                increment(high, low);
+                let (sum, carry) = VIP_TABLE[*low as usize].overflowing_add(*high);
+                *high = sum.wrapping_add(sum >> 1 | (carry as u8) << 7);
+                *high
+            }
+        }
+    }
+}
+
+/// 16-bit increment of R9
+fn increment(high: &mut u8, low: &mut u8) {
+    *low = low.wrapping_add(1);
+    if *low == 0 {
+        *high = high.wrapping_add(1);
+    }
+}
+
+/// Spreads the bits of a small seed so that nearby seeds give unrelated sequences
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = RandomSource::new(RngMode::Standard, 42);
+        let mut b = RandomSource::new(RngMode::Standard, 42);
+        let mut c = RandomSource::new(RngMode::Standard, 43);
+
+        let a: Vec<u8> = (0..16).map(|_| a.next_byte()).collect();
+        let b: Vec<u8> = (0..16).map(|_| b.next_byte()).collect();
+        let c: Vec<u8> = (0..16).map(|_| c.next_byte()).collect();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn vip_sequence() {
+        // R9 = 12D8, stepped through the interpreter's CXNN routine: INC R9 gives 12D9, table[D9] is 19,
+        // 19 + 12 = 2B, 2B + (2B >> 1) = 40, and so on
+        let mut rng = RandomSource::new(RngMode::CosmacVip, 0x12D8);
+        let bytes: Vec<u8> = (0..6).map(|_| rng.next_byte()).collect();
+        assert_eq!(bytes, [0x40, 0x2D, 0x48, 0x48, 0x89, 0xB3]);
+
+        // The interrupt steps through the table too
+        let mut rng = RandomSource::new(RngMode::CosmacVip, 0x12FF);
+        rng.tick();
+        assert_eq!(rng, RandomSource::CosmacVip { high: 0x13, low: 0x00 });
+    }
+}
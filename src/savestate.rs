@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use crate::quirks::{Platform, Quirks};
+use crate::rng::RandomSource;
+
+pub(crate) const SAVE_DIRECTORY: &str = "saves";
+
+#[derive(Error, Debug)]
+pub(crate) enum SaveStateError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid save state: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("no ROM loaded")]
+    NoRom,
+}
+
+/// Complete emulator state, see `Chip8::save_state` and `Chip8::load_state`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SaveState {
+    pub(crate) rom_hash: String,
+    pub(crate) registers: [u8; 16],
+    pub(crate) memory: Vec<u8>,
+    pub(crate) index: u16,
+    pub(crate) pc: u16,
+    pub(crate) stack: [u16; 16],
+    pub(crate) stack_ptr: u8,
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    /// One bit per pixel, one entry per row, leftmost pixel in the most significant bit
+    pub(crate) screen: Vec<u64>,
+    pub(crate) cycles: u64,
+    pub(crate) frame_cycles: u32,
+    pub(crate) vblank_wait: bool,
+    pub(crate) seed: u64,
+    pub(crate) rng: RandomSource,
+    pub(crate) platform: Platform,
+    pub(crate) quirks: Quirks,
+    pub(crate) tickrate: u32,
+}
+
+impl SaveState {
+    pub(crate) fn load(path: &Path) -> Result<SaveState, SaveStateError> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), SaveStateError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Where quick save slot `slot` of the ROM with the given hash is stored
+pub(crate) fn slot_path(rom_hash: &str, slot: u8) -> PathBuf {
+    Path::new(SAVE_DIRECTORY).join(rom_hash).join(format!("slot{slot}.json"))
+}
+
+pub(crate) fn pack_row(row: &[bool]) -> u64 {
+    row.iter().fold(0, |bits, &pixel| (bits << 1) | pixel as u64)
+}
+
+pub(crate) fn unpack_row(bits: u64, row: &mut [bool]) {
+    let width = row.len();
+    for (x, pixel) in row.iter_mut().enumerate() {
+        *pixel = bits & (1 << (width - 1 - x)) != 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip8::Chip8;
+
+    #[test]
+    fn load_restores_saved_state() {
+        let mut chip8 = Chip8::with_rom(&[0xC0, 0xFF, 0xD0, 0x05, 0x12, 0x00]);
+        chip8.run_frame().unwrap();
+        let state = chip8.save_state();
+
+        for _ in 0..10 {
+            chip8.run_frame().unwrap();
+        }
+        chip8.load_state(&state);
+        assert_eq!(chip8.save_state(), state);
+    }
+}
@@ -1,18 +1,69 @@
+use std::fmt::{Display, Formatter};
 use macroquad::prelude::*;
+use crate::palette::Palette;
+use crate::shader::PostProcess;
 
 
 pub(crate) const SCREEN_WIDTH: f32 = 64.0;
 pub(crate) const SCREEN_HEIGHT: f32 = 32.0;
+
+/// Post-processing applied to the framebuffer before it's shown, to hide the flicker caused by
+/// games erasing and redrawing sprites. Doesn't touch emulation state.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum DisplayFilter {
+    #[default]
+    None,
+    /// Pixels fade out over `persistence_frames` frames after being turned off
+    Persistence,
+    /// A pixel is lit if it was lit in this frame or the previous one
+    LastTwoFrames,
+}
+
+impl DisplayFilter {
+    pub(crate) const ALL: [DisplayFilter; 3] = [DisplayFilter::None, DisplayFilter::Persistence, DisplayFilter::LastTwoFrames];
+}
+
+impl Display for DisplayFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayFilter::None => write!(f, "None"),
+            DisplayFilter::Persistence => write!(f, "Persistence"),
+            DisplayFilter::LastTwoFrames => write!(f, "Last two frames"),
+        }
+    }
+}
+
 pub(crate) struct Screen {
     pub(crate) state: [[bool; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
-    pub(crate) show_grid: bool
+    pub(crate) show_grid: bool,
+    pub(crate) palette: Palette,
+    /// Only scale by whole multiples so that every CHIP-8 pixel is the same size
+    pub(crate) integer_scaling: bool,
+    pub(crate) filter: DisplayFilter,
+    pub(crate) persistence_frames: u32,
+    pub(crate) post_process: PostProcess,
+    /// Brightness of every pixel as shown in the last frame, 0.0 to 1.0
+    intensity: [[f32; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
+    previous_state: [[bool; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
+    image: Image,
+    /// Created on first draw, as it needs a graphics context
+    texture: Option<Texture2D>,
 }
 
 impl Default for Screen {
     fn default() -> Self {
         Self {
             show_grid: false,
-            state: [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize]
+            state: [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
+            palette: Palette::default(),
+            integer_scaling: false,
+            filter: DisplayFilter::None,
+            persistence_frames: 4,
+            post_process: PostProcess::default(),
+            intensity: [[0.0; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
+            previous_state: [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize],
+            image: Image::gen_image_color(SCREEN_WIDTH as u16, SCREEN_HEIGHT as u16, BLACK),
+            texture: None,
         }
     }
 }
@@ -20,35 +71,133 @@ impl Default for Screen {
 impl Screen {
 
     pub(crate) fn reset(&mut self) {
-        self.state = [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize]
+        self.state = [[false; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize];
+        self.previous_state = self.state;
+        self.intensity = [[0.0; SCREEN_WIDTH as usize]; SCREEN_HEIGHT as usize];
+    }
+
+    /// Advances the display filter by one frame and updates `intensity`
+    fn apply_filter(&mut self) {
+        let decay = 1.0 / self.persistence_frames.max(1) as f32;
+        let pixels = self.intensity.iter_mut().flatten()
+            .zip(self.state.iter().flatten().zip(self.previous_state.iter().flatten()));
+
+        for (intensity, (&on, &was_on)) in pixels {
+            *intensity = match self.filter {
+                _ if on => 1.0,
+                DisplayFilter::None => 0.0,
+                DisplayFilter::Persistence => (*intensity - decay).max(0.0),
+                DisplayFilter::LastTwoFrames => if was_on { 1.0 } else { 0.0 },
+            };
+        }
+        self.previous_state = self.state;
+    }
+
+    /// Largest area with the CHIP-8 aspect ratio that fits left of the side panel, centered in the free space
+    pub(crate) fn viewport(&self, rect: &egui_macroquad::egui::Rect) -> Rect {
+        let available_width = (screen_width() - rect.width()).max(0.0);
+        let available_height = screen_height();
+
+        let mut scale = (available_width / SCREEN_WIDTH).min(available_height / SCREEN_HEIGHT);
+        if self.integer_scaling && scale >= 1.0 {
+            scale = scale.floor();
+        }
+
+        let width = SCREEN_WIDTH * scale;
+        let height = SCREEN_HEIGHT * scale;
+        Rect::new(
+            ((available_width - width) / 2.0).floor(),
+            ((available_height - height) / 2.0).floor(),
+            width,
+            height,
+        )
     }
 
-    pub(crate) fn update(&self, rect: &egui_macroquad::egui::Rect) {
-        let mut y = 0.0;
-        let y_inc = screen_height() / SCREEN_HEIGHT;
+    pub(crate) fn update(&mut self, rect: &egui_macroquad::egui::Rect) {
+        self.apply_filter();
 
-        for row in self.state {
+        let [background, foreground, ..] = self.palette.colors;
+        for (pixel, &intensity) in self.image.get_image_data_mut().iter_mut().zip(self.intensity.iter().flatten()) {
+            *pixel = lerp_color(background, foreground, intensity).into();
+        }
+
+        let viewport = self.viewport(rect);
+        let texture = self.texture.get_or_insert_with(|| {
+            let texture = Texture2D::from_image(&self.image);
+            texture.set_filter(FilterMode::Nearest);
+            texture
+        });
+        texture.update(&self.image);
+
+        self.post_process.draw(texture, viewport);
+
+        if self.show_grid {
+            self.draw_grid(viewport);
+        }
+    }
+
+    fn draw_grid(&self, viewport: Rect) {
+        let x_inc = viewport.w / SCREEN_WIDTH;
+        let y_inc = viewport.h / SCREEN_HEIGHT;
+
+        for column in 0..=SCREEN_WIDTH as usize {
+            let x = viewport.x + column as f32 * x_inc;
+            draw_line(x, viewport.y, x, viewport.bottom(), 1.0, self.palette.grid);
+        }
+        for row in 0..=SCREEN_HEIGHT as usize {
+            let y = viewport.y + row as f32 * y_inc;
+            draw_line(viewport.x, y, viewport.right(), y, 1.0, self.palette.grid);
+        }
+    }
+}
+
+fn lerp_color(from: Color, to: Color, amount: f32) -> Color {
+    Color::new(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+        from.a + (to.a - from.a) * amount,
+    )
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let mut x = 0.0;
-            let x_inc = (screen_width() - rect.width()) / SCREEN_WIDTH;
+    #[test]
+    fn persistence_decays_over_frames() {
+        let mut screen = Screen {
+            filter: DisplayFilter::Persistence,
+            persistence_frames: 4,
+            ..Screen::default()
+        };
 
-            if self.show_grid {
-                draw_line(0.0, y, screen_width(), y, 1.0, GREEN);
-            }
+        screen.state[0][0] = true;
+        screen.apply_filter();
+        screen.state[0][0] = false;
 
-            for pixel in row {
-                if self.show_grid {
-                    draw_line(x, 0.0, x, screen_height(), 1.0, GREEN);
-                }
-                if pixel {
-                    draw_rectangle(x, y, x_inc, y_inc, WHITE);
-                } else {
-                    draw_rectangle(x, y, x_inc, y_inc, BLACK);
-                }
-                x += x_inc;
-            }
-            y += y_inc;
+        let mut intensities = vec![];
+        for _ in 0..4 {
+            screen.apply_filter();
+            intensities.push(screen.intensity[0][0]);
         }
+        assert_eq!(intensities, vec![0.75, 0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn last_two_frames_keeps_erased_sprite() {
+        let mut screen = Screen {
+            filter: DisplayFilter::LastTwoFrames,
+            ..Screen::default()
+        };
+
+        screen.state[1][1] = true;
+        screen.apply_filter();
+        screen.state[1][1] = false;
+        screen.apply_filter();
+        assert_eq!(screen.intensity[1][1], 1.0);
+
+        screen.apply_filter();
+        assert_eq!(screen.intensity[1][1], 0.0);
     }
 }
\ No newline at end of file
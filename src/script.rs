@@ -0,0 +1,146 @@
+//! Startup scripts for automating chipmate, one command per line:
+//!
+//! ```text
+//! # Lines starting with '#' are comments
+//! wait 120      # let 120 frames pass
+//! press 5       # hold CHIP-8 key 5 until it's released
+//! release 5
+//! screenshot
+//! save 1        # quick save to slot 1
+//! load 1
+//! pause
+//! resume
+//! quit
+//! ```
+
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+use crate::capture::Capture;
+use crate::chip8::Chip8;
+use crate::speed::RunState;
+
+#[derive(Error, Debug)]
+pub(crate) enum ScriptError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("line {0}: {1}")]
+    Parse(usize, String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    Wait(u32),
+    Press(u8),
+    Release(u8),
+    Screenshot,
+    Save(u8),
+    Load(u8),
+    Pause,
+    Resume,
+    Quit,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Script {
+    commands: Vec<Command>,
+    next: usize,
+    /// Frames left before the next command runs
+    wait: u32,
+    /// Keys held by the script, applied on top of the host keyboard
+    keys: u16,
+}
+
+impl Script {
+    pub(crate) fn load(path: &Path) -> Result<Script, ScriptError> {
+        Script::parse(&fs::read_to_string(path)?)
+    }
+
+    fn parse(source: &str) -> Result<Script, ScriptError> {
+        let mut commands = Vec::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(name) = words.next() else {
+                continue;
+            };
+            let argument = words.next();
+            let error = |message: &str| ScriptError::Parse(number + 1, message.to_owned());
+
+            let key = || argument.and_then(|key| u8::from_str_radix(key, 16).ok()).filter(|&key| key < 16)
+                .ok_or_else(|| error("expected a key from 0 to F"));
+            let frames = || argument.and_then(|frames| frames.parse().ok())
+                .ok_or_else(|| error("expected a number of frames"));
+            let slot = || argument.and_then(|slot| slot.parse().ok())
+                .ok_or_else(|| error("expected a slot number"));
+
+            commands.push(match name {
+                "wait" => Command::Wait(frames()?),
+                "press" => Command::Press(key()?),
+                "release" => Command::Release(key()?),
+                "screenshot" => Command::Screenshot,
+                "save" => Command::Save(slot()?),
+                "load" => Command::Load(slot()?),
+                "pause" => Command::Pause,
+                "resume" => Command::Resume,
+                "quit" => Command::Quit,
+                _ => return Err(error(&format!("unknown command '{name}'"))),
+            });
+        }
+
+        Ok(Script { commands, ..Script::default() })
+    }
+
+    /// Runs the commands due this frame and presses the held keys. Call after
+    /// `Chip8::process_input`. Returns false once the script asks to quit.
+    pub(crate) fn update(&mut self, chip8: &mut Chip8, capture: &Capture) -> bool {
+        while self.wait == 0 {
+            let Some(&command) = self.commands.get(self.next) else {
+                break;
+            };
+            self.next += 1;
+
+            match command {
+                Command::Wait(frames) => self.wait = frames,
+                Command::Press(key) => self.keys |= 1 << key,
+                Command::Release(key) => self.keys &= !(1 << key),
+                Command::Screenshot => capture.screenshot(&chip8.screen),
+                Command::Save(slot) => {
+                    chip8.state_slot = slot;
+                    if let Err(e) = chip8.quick_save() {
+                        log::error!("{e}");
+                    }
+                }
+                Command::Load(slot) => {
+                    chip8.state_slot = slot;
+                    if let Err(e) = chip8.quick_load() {
+                        log::error!("{e}");
+                    }
+                }
+                Command::Pause => chip8.speed.state = RunState::Paused,
+                Command::Resume => chip8.speed.state = RunState::Running,
+                Command::Quit => return false,
+            }
+        }
+        self.wait = self.wait.saturating_sub(1);
+
+        for (key, down) in chip8.keypad.iter_mut().enumerate() {
+            *down |= self.keys & (1 << key) != 0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_and_reports_line() {
+        let script = Script::parse("# test\nwait 10\npress a # fire\n\nquit").unwrap();
+        assert_eq!(script.commands, [Command::Wait(10), Command::Press(0xA), Command::Quit]);
+
+        assert!(matches!(Script::parse("wait 1\npress 16"), Err(ScriptError::Parse(2, _))));
+    }
+}
@@ -0,0 +1,214 @@
+use std::fmt::{Display, Formatter};
+use macroquad::prelude::*;
+
+/// Strength of each post-processing effect, 0.0 turns an effect off
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct ShaderParams {
+    pub(crate) scanlines: f32,
+    pub(crate) curvature: f32,
+    pub(crate) bloom: f32,
+    pub(crate) vignette: f32,
+    pub(crate) lcd_grid: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum ShaderPreset {
+    /// Draws the screen texture as is
+    #[default]
+    None,
+    Scanlines,
+    Crt,
+    Lcd,
+    Custom,
+}
+
+pub(crate) struct PostProcess {
+    pub(crate) preset: ShaderPreset,
+    pub(crate) params: ShaderParams,
+    /// Compiled on first use, as it needs a graphics context
+    material: Option<Material>,
+    /// Set when compiling the shader failed so it isn't retried every frame
+    failed: bool,
+}
+
+impl ShaderPreset {
+    pub(crate) const ALL: [ShaderPreset; 5] = [
+        ShaderPreset::None,
+        ShaderPreset::Scanlines,
+        ShaderPreset::Crt,
+        ShaderPreset::Lcd,
+        ShaderPreset::Custom,
+    ];
+
+    /// Parameters the preset starts with. `None` for presets that keep the current ones.
+    pub(crate) fn params(&self) -> Option<ShaderParams> {
+        let off = ShaderParams { scanlines: 0.0, curvature: 0.0, bloom: 0.0, vignette: 0.0, lcd_grid: 0.0 };
+        match self {
+            ShaderPreset::None => Some(off),
+            ShaderPreset::Scanlines => Some(ShaderParams { scanlines: 0.5, ..off }),
+            ShaderPreset::Crt => Some(ShaderParams { scanlines: 0.4, curvature: 0.5, bloom: 0.4, vignette: 0.5, ..off }),
+            ShaderPreset::Lcd => Some(ShaderParams { lcd_grid: 0.6, bloom: 0.1, ..off }),
+            ShaderPreset::Custom => None,
+        }
+    }
+}
+
+impl Display for ShaderPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ShaderPreset::None => "None",
+            ShaderPreset::Scanlines => "Scanlines",
+            ShaderPreset::Crt => "CRT",
+            ShaderPreset::Lcd => "LCD",
+            ShaderPreset::Custom => "Custom",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            preset: ShaderPreset::None,
+            params: ShaderPreset::None.params().unwrap(),
+            material: None,
+            failed: false,
+        }
+    }
+}
+
+impl PostProcess {
+    pub(crate) fn set_preset(&mut self, preset: ShaderPreset) {
+        self.preset = preset;
+        if let Some(params) = preset.params() {
+            self.params = params;
+        }
+    }
+
+    /// Draws `texture` into `dest`, through the shader unless the preset is `None`
+    pub(crate) fn draw(&mut self, texture: &Texture2D, dest: Rect) {
+        let params = DrawTextureParams {
+            dest_size: Some(dest.size()),
+            ..Default::default()
+        };
+
+        let shader_params = self.params;
+        let material = match self.preset {
+            ShaderPreset::None => None,
+            _ => self.material(),
+        };
+        let Some(material) = material else {
+            draw_texture_ex(texture, dest.x, dest.y, WHITE, params);
+            return;
+        };
+
+        material.set_uniform("TextureSize", texture.size());
+        material.set_uniform("Scanlines", shader_params.scanlines);
+        material.set_uniform("Curvature", shader_params.curvature);
+        material.set_uniform("Bloom", shader_params.bloom);
+        material.set_uniform("Vignette", shader_params.vignette);
+        material.set_uniform("LcdGrid", shader_params.lcd_grid);
+
+        gl_use_material(material);
+        draw_texture_ex(texture, dest.x, dest.y, WHITE, params);
+        gl_use_default_material();
+    }
+
+    fn material(&mut self) -> Option<&Material> {
+        if self.material.is_none() && !self.failed {
+            let uniforms = ["Scanlines", "Curvature", "Bloom", "Vignette", "LcdGrid"]
+                .into_iter()
+                .map(|name| UniformDesc::new(name, UniformType::Float1))
+                .chain([UniformDesc::new("TextureSize", UniformType::Float2)])
+                .collect();
+
+            let material = load_material(
+                ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+                MaterialParams { uniforms, ..Default::default() },
+            );
+            match material {
+                Ok(material) => self.material = Some(material),
+                Err(e) => {
+                    log::error!("Failed to compile screen shader: {e}");
+                    self.failed = true;
+                }
+            }
+        }
+        self.material.as_ref()
+    }
+}
+
+const VERTEX_SHADER: &str = "#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying mediump vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+";
+
+const FRAGMENT_SHADER: &str = "#version 100
+precision mediump float;
+
+varying lowp vec4 color;
+varying mediump vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 TextureSize;
+uniform float Scanlines;
+uniform float Curvature;
+uniform float Bloom;
+uniform float Vignette;
+uniform float LcdGrid;
+
+const float PI = 3.14159265;
+
+vec2 curve(vec2 uv) {
+    uv = uv * 2.0 - 1.0;
+    vec2 offset = abs(uv.yx) / vec2(6.0, 4.0);
+    uv = uv + uv * offset * offset * Curvature * 2.0;
+    return uv * 0.5 + 0.5;
+}
+
+vec3 glow(vec2 uv) {
+    vec2 texel = 1.0 / TextureSize;
+    vec3 sum = vec3(0.0);
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            sum += texture2D(Texture, uv + vec2(float(x), float(y)) * texel).rgb;
+        }
+    }
+    return sum / 9.0;
+}
+
+void main() {
+    vec2 coord = curve(uv);
+    if (coord.x < 0.0 || coord.x > 1.0 || coord.y < 0.0 || coord.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec3 res = texture2D(Texture, coord).rgb;
+    res += glow(coord) * Bloom;
+
+    // Position inside the current CHIP-8 pixel, 0.0 to 1.0
+    vec2 cell = fract(coord * TextureSize);
+    res *= 1.0 - Scanlines * (1.0 - sin(cell.y * PI));
+    float edge = step(0.1, cell.x) * step(0.1, cell.y);
+    res *= 1.0 - LcdGrid * (1.0 - edge);
+
+    float vignette = coord.x * coord.y * (1.0 - coord.x) * (1.0 - coord.y);
+    res *= mix(1.0, clamp(pow(16.0 * vignette, 0.3), 0.0, 1.0), Vignette);
+
+    gl_FragColor = vec4(res * color.rgb, 1.0);
+}
+";
@@ -0,0 +1,155 @@
+use std::fmt::{Display, Formatter};
+use crate::bus::{Access, BusObserver};
+
+/// Part of an instruction that has been executed
+const EXECUTED: u8 = 1;
+const WRITTEN: u8 = 2;
+/// First byte of an executed instruction
+const INSTRUCTION: u8 = 4;
+/// Involved in a self-modifying code event
+const MARKED: u8 = 8;
+/// Events already logged for this address
+const REPORTED_WRITE: u8 = 16;
+const REPORTED_EXECUTE: u8 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SmcKind {
+    /// The program wrote to an address it had executed before
+    WriteToCode,
+    /// The program executed an address it had written before
+    ExecuteWritten,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SmcEvent {
+    pub(crate) kind: SmcKind,
+    pub(crate) address: u16,
+    /// Instruction that did the write or the execution
+    pub(crate) pc: u16,
+}
+
+/// Notices self-modifying code and code/data overlap. Only the first event of each kind
+/// is kept for every address.
+pub(crate) struct SmcDetector {
+    /// Pause emulation whenever an event happens
+    pub(crate) break_on_event: bool,
+    pub(crate) events: Vec<SmcEvent>,
+    flags: Vec<u8>,
+    pending_break: bool,
+}
+
+impl Default for SmcDetector {
+    fn default() -> Self {
+        Self {
+            break_on_event: false,
+            events: Vec::new(),
+            flags: vec![0; 0x1000],
+            pending_break: false,
+        }
+    }
+}
+
+impl Display for SmcEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            SmcKind::WriteToCode => write!(f, "{:03X} wrote to code at {:03X}", self.pc, self.address),
+            SmcKind::ExecuteWritten => write!(f, "Executed {:03X}, which was written as data", self.address),
+        }
+    }
+}
+
+impl SmcDetector {
+    /// Forgets everything, for when memory is reinitialized
+    pub(crate) fn clear(&mut self) {
+        self.flags.fill(0);
+        self.events.clear();
+        self.pending_break = false;
+    }
+
+    fn event(&mut self, kind: SmcKind, address: usize, pc: u16) {
+        if self.break_on_event {
+            self.pending_break = true;
+        }
+
+        let reported = match kind {
+            SmcKind::WriteToCode => REPORTED_WRITE,
+            SmcKind::ExecuteWritten => REPORTED_EXECUTE,
+        };
+        if self.flags[address] & reported != 0 {
+            return;
+        }
+        self.flags[address] |= reported | MARKED;
+
+        let event = SmcEvent { kind, address: address as u16, pc };
+        log::warn!("Self-modifying code: {event}");
+        self.events.push(event);
+    }
+
+    /// Returns true once after an event if breaking is on
+    pub(crate) fn take_break(&mut self) -> bool {
+        std::mem::take(&mut self.pending_break)
+    }
+
+    /// Whether an instruction has been executed starting at this address
+    pub(crate) fn is_instruction(&self, address: usize) -> bool {
+        self.flags[address] & INSTRUCTION != 0
+    }
+
+    /// Whether the bytes of the instruction at this address were involved in an event
+    pub(crate) fn is_marked(&self, address: usize) -> bool {
+        (self.flags[address] | self.flags[(address + 1) % self.flags.len()]) & MARKED != 0
+    }
+}
+
+impl BusObserver for SmcDetector {
+    fn access(&mut self, address: u16, access: Access, _value: u8, pc: u16) {
+        let address = address as usize;
+        match access {
+            Access::Execute => {
+                if address == pc as usize {
+                    self.flags[address] |= INSTRUCTION;
+                }
+                if self.flags[address] & WRITTEN != 0 {
+                    self.event(SmcKind::ExecuteWritten, address, pc);
+                }
+                self.flags[address] |= EXECUTED;
+            }
+            Access::Write => {
+                if self.flags[address] & EXECUTED != 0 {
+                    self.event(SmcKind::WriteToCode, address, pc);
+                }
+                self.flags[address] |= WRITTEN;
+            }
+            Access::Read => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_both_directions_once() {
+        let mut detector = SmcDetector { break_on_event: true, ..SmcDetector::default() };
+        let fetch = |detector: &mut SmcDetector, pc: u16| {
+            detector.access(pc, Access::Execute, 0, pc);
+            detector.access(pc + 1, Access::Execute, 0, pc);
+        };
+
+        fetch(&mut detector, 0x200);
+        detector.access(0x300, Access::Write, 0, 0x202);
+        assert!(detector.events.is_empty());
+
+        detector.access(0x201, Access::Write, 0, 0x202);
+        detector.access(0x201, Access::Write, 0, 0x202);
+        fetch(&mut detector, 0x300);
+        assert_eq!(detector.events, [
+            SmcEvent { kind: SmcKind::WriteToCode, address: 0x201, pc: 0x202 },
+            SmcEvent { kind: SmcKind::ExecuteWritten, address: 0x300, pc: 0x300 },
+        ]);
+        assert!(detector.is_marked(0x200));
+        assert!(detector.take_break());
+        assert!(!detector.take_break());
+    }
+}
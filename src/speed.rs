@@ -0,0 +1,146 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum RunState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Single step requested while paused
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Advance {
+    /// One instruction
+    Cycle,
+    /// A whole frame's worth of instructions plus a timer tick
+    Frame,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum PlaybackSpeed {
+    Quarter,
+    Half,
+    #[default]
+    Normal,
+}
+
+/// Decides how many emulated frames run per host frame
+pub(crate) struct SpeedControl {
+    pub(crate) state: RunState,
+    pub(crate) playback: PlaybackSpeed,
+    /// Speed while the turbo key is held
+    pub(crate) turbo_multiplier: u32,
+    pub(crate) turbo: bool,
+    advance: Option<Advance>,
+    /// Fractional frames carried over to the next host frame in slow motion
+    accumulator: f32,
+}
+
+impl Default for SpeedControl {
+    fn default() -> Self {
+        Self {
+            state: RunState::default(),
+            playback: PlaybackSpeed::default(),
+            turbo_multiplier: 4,
+            turbo: false,
+            advance: None,
+            accumulator: 0.0,
+        }
+    }
+}
+
+impl PlaybackSpeed {
+    pub(crate) const ALL: [PlaybackSpeed; 3] = [PlaybackSpeed::Quarter, PlaybackSpeed::Half, PlaybackSpeed::Normal];
+
+    pub(crate) fn factor(&self) -> f32 {
+        match self {
+            PlaybackSpeed::Quarter => 0.25,
+            PlaybackSpeed::Half => 0.5,
+            PlaybackSpeed::Normal => 1.0,
+        }
+    }
+}
+
+impl Display for PlaybackSpeed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x", self.factor())
+    }
+}
+
+impl SpeedControl {
+    pub(crate) fn is_paused(&self) -> bool {
+        self.state == RunState::Paused
+    }
+
+    pub(crate) fn toggle_pause(&mut self) {
+        self.state = match self.state {
+            RunState::Running => RunState::Paused,
+            RunState::Paused => RunState::Running,
+        };
+    }
+
+    /// Pauses and queues a single step for the next host frame
+    pub(crate) fn request(&mut self, advance: Advance) {
+        self.state = RunState::Paused;
+        self.advance = Some(advance);
+    }
+
+    pub(crate) fn take_advance(&mut self) -> Option<Advance> {
+        self.advance.take()
+    }
+
+    /// Emulated frames per host frame, 0 while paused
+    pub(crate) fn factor(&self) -> f32 {
+        match self.state {
+            RunState::Paused => 0.0,
+            RunState::Running if self.turbo => self.turbo_multiplier as f32,
+            RunState::Running => self.playback.factor(),
+        }
+    }
+
+    /// How many emulated frames to run during this host frame
+    pub(crate) fn frames_due(&mut self) -> u32 {
+        if self.is_paused() {
+            self.accumulator = 0.0;
+            return 0;
+        }
+
+        self.accumulator += self.factor();
+        let frames = self.accumulator.floor();
+        self.accumulator -= frames;
+        frames as u32
+    }
+}
+
+impl Display for SpeedControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.state {
+            RunState::Paused => write!(f, "Paused"),
+            RunState::Running if self.turbo => write!(f, "{}x (turbo)", self.turbo_multiplier),
+            RunState::Running => write!(f, "{}", self.playback),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_motion_and_turbo() {
+        let mut speed = SpeedControl {
+            playback: PlaybackSpeed::Quarter,
+            ..SpeedControl::default()
+        };
+        let frames: u32 = (0..8).map(|_| speed.frames_due()).sum();
+        assert_eq!(frames, 2);
+
+        speed.turbo = true;
+        assert_eq!(speed.frames_due(), 4);
+
+        speed.request(Advance::Frame);
+        assert_eq!(speed.frames_due(), 0);
+        assert_eq!(speed.take_advance(), Some(Advance::Frame));
+        assert_eq!(speed.take_advance(), None);
+    }
+}
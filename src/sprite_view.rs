@@ -0,0 +1,230 @@
+use std::path::Path;
+use egui_macroquad::egui::{self, Color32, Rect, Sense, Vec2};
+use image::{GrayImage, ImageError, Luma};
+use rfd::FileDialog;
+use crate::chip8::Chip8;
+
+/// Sprites per row of an exported sheet
+const SHEET_COLUMNS: usize = 8;
+/// Size of a sprite pixel on screen
+const PIXEL_SIZE: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum SpriteSize {
+    /// 8 pixels wide, 1 to 15 rows, as drawn by DXYN
+    #[default]
+    Small,
+    /// 16x16, as drawn by SUPER-CHIP's DXY0
+    Large,
+}
+
+/// Shows memory as sprites, starting at I or at a fixed address. Clicking a pixel flips its bit.
+pub(crate) struct SpriteView {
+    pub(crate) follow_index: bool,
+    pub(crate) address: u16,
+    pub(crate) size: SpriteSize,
+    /// Rows of small sprites
+    pub(crate) rows: usize,
+    /// Sprites shown one after another
+    pub(crate) count: usize,
+}
+
+impl Default for SpriteView {
+    fn default() -> Self {
+        Self {
+            follow_index: true,
+            address: 0x200,
+            size: SpriteSize::default(),
+            rows: 5,
+            count: 1,
+        }
+    }
+}
+
+impl SpriteSize {
+    fn width(&self) -> usize {
+        match self {
+            SpriteSize::Small => 8,
+            SpriteSize::Large => 16,
+        }
+    }
+
+    fn height(&self, rows: usize) -> usize {
+        match self {
+            SpriteSize::Small => rows,
+            SpriteSize::Large => 16,
+        }
+    }
+
+    /// Bytes taken by one sprite
+    fn bytes(&self, rows: usize) -> usize {
+        self.height(rows) * self.width() / 8
+    }
+}
+
+impl SpriteView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("Sprites").open(open).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.follow_index, "Follow I");
+                ui.add_enabled(
+                    !self.follow_index,
+                    egui::DragValue::new(&mut self.address).hexadecimal(3, false, true).clamp_range(0..=0xFFF),
+                );
+                ui.label("Address");
+            });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.size, SpriteSize::Small, "8 wide");
+                ui.selectable_value(&mut self.size, SpriteSize::Large, "16x16");
+                ui.add_enabled(self.size == SpriteSize::Small, egui::DragValue::new(&mut self.rows).clamp_range(1..=15));
+                ui.label("Rows");
+                ui.add(egui::DragValue::new(&mut self.count).clamp_range(1..=64));
+                ui.label("Sprites");
+            });
+
+            ui.horizontal(|ui| {
+                let dialog = || FileDialog::new().add_filter("PNG sprite sheet", &["png"]);
+                if ui.button("Export PNG").clicked() {
+                    if let Some(path) = dialog().save_file() {
+                        if let Err(e) = self.export(chip8, &path) {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+                if ui.button("Import PNG").on_hover_text("Writes the sheet's sprites to memory from the current address").clicked() {
+                    if let Some(path) = dialog().pick_file() {
+                        if let Err(e) = self.import(chip8, &path) {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+            });
+            ui.separator();
+
+            self.sprites(ui, chip8);
+        });
+    }
+
+    fn start(&self, chip8: &Chip8) -> usize {
+        if self.follow_index { chip8.index() as usize } else { self.address as usize }
+    }
+
+    fn sprites(&self, ui: &mut egui::Ui, chip8: &mut Chip8) {
+        let (width, height) = (self.size.width(), self.size.height(self.rows));
+        let columns = self.count.min(SHEET_COLUMNS);
+        let sheet_rows = self.count.div_ceil(SHEET_COLUMNS);
+        // One pixel gap between sprites
+        let cell = Vec2::new((width + 1) as f32, (height + 1) as f32) * PIXEL_SIZE;
+
+        let (response, painter) = ui.allocate_painter(cell * Vec2::new(columns as f32, sheet_rows as f32), Sense::click());
+        let origin = response.rect.min;
+        let start = self.start(chip8);
+        let foreground = ui.visuals().strong_text_color();
+
+        for sprite in 0..self.count {
+            let offset = origin + cell * Vec2::new((sprite % SHEET_COLUMNS) as f32, (sprite / SHEET_COLUMNS) as f32);
+            let base = start + sprite * self.size.bytes(self.rows);
+            painter.rect_filled(Rect::from_min_size(offset, Vec2::new(width as f32, height as f32) * PIXEL_SIZE), 0.0, Color32::BLACK);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let (address, bit) = pixel_location(base, width, x, y);
+                    if chip8.memory[address % chip8.memory.len()] & bit != 0 {
+                        let min = offset + Vec2::new(x as f32, y as f32) * PIXEL_SIZE;
+                        painter.rect_filled(Rect::from_min_size(min, Vec2::splat(PIXEL_SIZE)), 0.0, foreground);
+                    }
+                }
+            }
+        }
+
+        let Some(position) = response.interact_pointer_pos().filter(|_| response.clicked()) else {
+            return;
+        };
+        let relative = position - origin;
+        let (column, row) = ((relative.x / cell.x) as usize, (relative.y / cell.y) as usize);
+        let (x, y) = (((relative.x % cell.x) / PIXEL_SIZE) as usize, ((relative.y % cell.y) / PIXEL_SIZE) as usize);
+        let sprite = row * SHEET_COLUMNS + column;
+        if sprite < self.count && x < width && y < height {
+            let (address, bit) = pixel_location(start + sprite * self.size.bytes(self.rows), width, x, y);
+            let size = chip8.memory.len();
+            chip8.memory[address % size] ^= bit;
+        }
+    }
+
+    fn export(&self, chip8: &Chip8, path: &Path) -> Result<(), ImageError> {
+        let bytes = self.size.bytes(self.rows) * self.count;
+        let start = self.start(chip8);
+        let data: Vec<u8> = (start..start + bytes).map(|address| chip8.memory[address % chip8.memory.len()]).collect();
+        encode_sheet(&data, self.size.width(), self.size.height(self.rows)).save(path)
+    }
+
+    fn import(&self, chip8: &mut Chip8, path: &Path) -> Result<(), ImageError> {
+        let sheet = image::open(path)?.into_luma8();
+        let data = decode_sheet(&sheet, self.size.width(), self.size.height(self.rows));
+        let start = self.start(chip8);
+        let size = chip8.memory.len();
+        log::info!("Imported {} bytes of sprites from {}", data.len(), path.display());
+        for (i, byte) in data.into_iter().enumerate() {
+            chip8.memory[(start + i) % size] = byte;
+        }
+        Ok(())
+    }
+}
+
+/// Address and bit mask of pixel (x, y) of the sprite at `base`, not wrapped to the memory size
+fn pixel_location(base: usize, width: usize, x: usize, y: usize) -> (usize, u8) {
+    (base + y * width / 8 + x / 8, 0x80 >> (x % 8))
+}
+
+/// Lays sprites out in rows of `SHEET_COLUMNS`, white on black
+fn encode_sheet(data: &[u8], width: usize, height: usize) -> GrayImage {
+    let sprite_bytes = width * height / 8;
+    let count = data.len() / sprite_bytes;
+    let columns = count.clamp(1, SHEET_COLUMNS);
+    let rows = count.div_ceil(SHEET_COLUMNS).max(1);
+
+    GrayImage::from_fn((columns * width) as u32, (rows * height) as u32, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let sprite = (y / height) * SHEET_COLUMNS + x / width;
+        if sprite >= count {
+            return Luma([0]);
+        }
+        let (address, bit) = pixel_location(sprite * sprite_bytes, width, x % width, y % height);
+        Luma([if data[address] & bit != 0 { 255 } else { 0 }])
+    })
+}
+
+/// Reads every whole sprite of a sheet, row by row. Pixels brighter than 50% are set.
+fn decode_sheet(sheet: &GrayImage, width: usize, height: usize) -> Vec<u8> {
+    let (columns, rows) = (sheet.width() as usize / width, sheet.height() as usize / height);
+    let mut data = vec![0; columns * rows * width * height / 8];
+
+    for sprite in 0..columns * rows {
+        let (left, top) = ((sprite % columns) * width, (sprite / columns) * height);
+        for y in 0..height {
+            for x in 0..width {
+                if sheet.get_pixel((left + x) as u32, (top + y) as u32)[0] >= 128 {
+                    let (address, bit) = pixel_location(sprite * width * height / 8, width, x, y);
+                    data[address] |= bit;
+                }
+            }
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheet_round_trip() {
+        let small: Vec<u8> = (0..15).map(|i| i * 17).collect();
+        let sheet = encode_sheet(&small, 8, 5);
+        assert_eq!(sheet.dimensions(), (24, 5));
+        assert_eq!(decode_sheet(&sheet, 8, 5), small);
+
+        let large: Vec<u8> = (0..32).collect();
+        assert_eq!(decode_sheet(&encode_sheet(&large, 16, 16), 16, 16), large);
+    }
+}
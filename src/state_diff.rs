@@ -0,0 +1,252 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::path::PathBuf;
+use egui_macroquad::egui::{self, Color32, Rect, Sense, Vec2};
+use rfd::FileDialog;
+use crate::chip8::Chip8;
+use crate::savestate::{slot_path, unpack_row, SaveState};
+use crate::screen::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/// Size of a screen pixel in the overlay
+const PIXEL_SIZE: f32 = 5.0;
+/// Bytes of a changed range shown before it's cut short
+const MAX_BYTES_SHOWN: usize = 8;
+
+/// A register, timer or other single value that differs
+#[derive(Debug, PartialEq)]
+pub(crate) struct FieldChange {
+    pub(crate) name: String,
+    pub(crate) before: String,
+    pub(crate) after: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct StateDiff {
+    pub(crate) fields: Vec<FieldChange>,
+    /// Runs of consecutive changed bytes
+    pub(crate) memory: Vec<Range<usize>>,
+    /// (x, y) of every pixel that differs
+    pub(crate) pixels: Vec<(usize, usize)>,
+}
+
+/// Differences going from state `a` to state `b`
+pub(crate) fn diff(a: &SaveState, b: &SaveState) -> StateDiff {
+    let mut fields = Vec::new();
+    let mut field = |name: String, before: String, after: String| {
+        if before != after {
+            fields.push(FieldChange { name, before, after });
+        }
+    };
+
+    for x in 0..16 {
+        field(format!("V{x:X}"), format!("{:02X}", a.registers[x]), format!("{:02X}", b.registers[x]));
+    }
+    field("I".to_owned(), format!("{:03X}", a.index), format!("{:03X}", b.index));
+    field("PC".to_owned(), format!("{:03X}", a.pc), format!("{:03X}", b.pc));
+    field("Stack".to_owned(), stack(a), stack(b));
+    field("DT".to_owned(), a.delay_timer.to_string(), b.delay_timer.to_string());
+    field("ST".to_owned(), a.sound_timer.to_string(), b.sound_timer.to_string());
+    field("Cycles".to_owned(), a.cycles.to_string(), b.cycles.to_string());
+
+    let mut memory: Vec<Range<usize>> = Vec::new();
+    for address in (0..a.memory.len().min(b.memory.len())).filter(|&address| a.memory[address] != b.memory[address]) {
+        match memory.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => memory.push(address..address + 1),
+        }
+    }
+
+    let (screen_a, screen_b) = (screen(a), screen(b));
+    let mut pixels = Vec::new();
+    for (y, (row_a, row_b)) in screen_a.iter().zip(&screen_b).enumerate() {
+        pixels.extend((0..row_a.len()).filter(|&x| row_a[x] != row_b[x]).map(|x| (x, y)));
+    }
+
+    StateDiff { fields, memory, pixels }
+}
+
+fn stack(state: &SaveState) -> String {
+    let depth = (state.stack_ptr as usize).min(state.stack.len());
+    let addresses: Vec<String> = state.stack[..depth].iter().map(|address| format!("{address:03X}")).collect();
+    format!("[{}]", addresses.join(" "))
+}
+
+fn screen(state: &SaveState) -> Vec<[bool; SCREEN_WIDTH as usize]> {
+    state.screen.iter().take(SCREEN_HEIGHT as usize).map(|&bits| {
+        let mut row = [false; SCREEN_WIDTH as usize];
+        unpack_row(bits, &mut row);
+        row
+    }).collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+enum Source {
+    #[default]
+    Now,
+    /// Taken in the diff window
+    Snapshot,
+    Slot(u8),
+    File(PathBuf),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Now => write!(f, "Now"),
+            Source::Snapshot => write!(f, "Snapshot"),
+            Source::Slot(slot) => write!(f, "Slot {slot}"),
+            Source::File(path) => write!(f, "{}", path.file_name().unwrap_or_default().to_string_lossy()),
+        }
+    }
+}
+
+/// One of the two states being compared. Slots and files are read when picked.
+#[derive(Default)]
+struct Side {
+    source: Source,
+    loaded: Option<SaveState>,
+}
+
+/// Compares two save states, or the running state with a snapshot
+pub(crate) struct StateDiffView {
+    a: Side,
+    b: Side,
+    snapshot: Option<SaveState>,
+}
+
+impl Default for StateDiffView {
+    fn default() -> Self {
+        Self {
+            a: Side { source: Source::Snapshot, loaded: None },
+            b: Side::default(),
+            snapshot: None,
+        }
+    }
+}
+
+impl StateDiffView {
+    pub(crate) fn window(&mut self, ctx: &egui::Context, open: &mut bool, chip8: &mut Chip8) {
+        egui::Window::new("State diff").open(open).show(ctx, |ui| {
+            if ui.button("Take snapshot").clicked() {
+                self.snapshot = Some(chip8.save_state());
+            }
+            side_ui(ui, "From", &mut self.a, chip8);
+            side_ui(ui, "To", &mut self.b, chip8);
+            ui.separator();
+
+            // Built once a frame, and only when one side shows the running state
+            let now = [&self.a, &self.b].iter().any(|side| side.source == Source::Now).then(|| chip8.save_state());
+            let (Some(a), Some(b)) = (self.state(&self.a, now.as_ref()), self.state(&self.b, now.as_ref())) else {
+                ui.label("Pick two states to compare");
+                return;
+            };
+            let diff = diff(a, b);
+
+            ui.collapsing(format!("Registers ({})", diff.fields.len()), |ui| {
+                egui::Grid::new("diff_fields").striped(true).show(ui, |ui| {
+                    for change in &diff.fields {
+                        ui.label(&change.name);
+                        ui.monospace(&change.before);
+                        ui.monospace(&change.after);
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.collapsing(format!("Memory ({} ranges)", diff.memory.len()), |ui| {
+                egui::ScrollArea::vertical().id_source("diff_memory").max_height(200.0).show(ui, |ui| {
+                    for range in &diff.memory {
+                        ui.monospace(format!(
+                            "{:03X}-{:03X}  {} → {}",
+                            range.start, range.end - 1, bytes(&a.memory[range.clone()]), bytes(&b.memory[range.clone()]),
+                        ));
+                    }
+                });
+            });
+            ui.collapsing(format!("Screen ({} pixels)", diff.pixels.len()), |ui| {
+                overlay(ui, b, &diff.pixels);
+            });
+        });
+    }
+
+    fn state<'a>(&'a self, side: &'a Side, now: Option<&'a SaveState>) -> Option<&'a SaveState> {
+        match side.source {
+            Source::Now => now,
+            Source::Snapshot => self.snapshot.as_ref(),
+            Source::Slot(_) | Source::File(_) => side.loaded.as_ref(),
+        }
+    }
+}
+
+fn side_ui(ui: &mut egui::Ui, label: &str, side: &mut Side, chip8: &Chip8) {
+    let mut picked = None;
+    egui::ComboBox::from_label(label)
+        .selected_text(side.source.to_string())
+        .show_ui(ui, |ui| {
+            for source in [Source::Now, Source::Snapshot].into_iter().chain((0..10).map(Source::Slot)) {
+                if ui.selectable_label(side.source == source, source.to_string()).clicked() {
+                    picked = Some(source);
+                }
+            }
+            if ui.selectable_label(false, "File…").clicked() {
+                picked = FileDialog::new().add_filter("chipmate save state", &["json"]).pick_file().map(Source::File);
+            }
+        });
+
+    let Some(source) = picked else { return };
+    let path = match &source {
+        Source::Slot(slot) => chip8.rom_hash.as_deref().map(|hash| slot_path(hash, *slot)),
+        Source::File(path) => Some(path.clone()),
+        Source::Now | Source::Snapshot => None,
+    };
+    side.loaded = path.and_then(|path| SaveState::load(&path).map_err(|e| log::error!("{e}")).ok());
+    side.source = source;
+}
+
+fn bytes(bytes: &[u8]) -> String {
+    let mut text: Vec<String> = bytes.iter().take(MAX_BYTES_SHOWN).map(|byte| format!("{byte:02X}")).collect();
+    if bytes.len() > MAX_BYTES_SHOWN {
+        text.push("…".to_owned());
+    }
+    text.join(" ")
+}
+
+/// Screen of `state` with the pixels that changed outlined in red
+fn overlay(ui: &mut egui::Ui, state: &SaveState, changed: &[(usize, usize)]) {
+    let size = Vec2::new(SCREEN_WIDTH, SCREEN_HEIGHT) * PIXEL_SIZE;
+    let (response, painter) = ui.allocate_painter(size, Sense::hover());
+    painter.rect_filled(response.rect, 0.0, Color32::BLACK);
+
+    let pixel = |x: usize, y: usize| {
+        Rect::from_min_size(response.rect.min + Vec2::new(x as f32, y as f32) * PIXEL_SIZE, Vec2::splat(PIXEL_SIZE))
+    };
+    for (y, row) in screen(state).iter().enumerate() {
+        for x in (0..row.len()).filter(|&x| row[x]) {
+            painter.rect_filled(pixel(x, y), 0.0, Color32::GRAY);
+        }
+    }
+    for &(x, y) in changed {
+        painter.rect_stroke(pixel(x, y), 0.0, (1.0, Color32::RED));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_registers_memory_and_pixels() {
+        let mut chip8 = Chip8::default();
+        let before = chip8.save_state();
+        chip8.registers[3] = 7;
+        chip8.memory[0x300] = 1;
+        chip8.memory[0x301] = 2;
+        chip8.memory[0x305] = 3;
+        chip8.screen.state[2][5] = true;
+        let after = chip8.save_state();
+
+        let diff = diff(&before, &after);
+        assert_eq!(diff.fields, [FieldChange { name: "V3".to_owned(), before: "00".to_owned(), after: "07".to_owned() }]);
+        assert_eq!(diff.memory, [0x300..0x302, 0x305..0x306]);
+        assert_eq!(diff.pixels, [(5, 2)]);
+    }
+}
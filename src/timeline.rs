@@ -0,0 +1,137 @@
+use crate::savestate::SaveState;
+
+/// Recorded execution for time-travel debugging. A snapshot every `interval` instructions and
+/// every keypad change are enough to get back to any instruction since recording began,
+/// by loading the snapshot before it and re-executing.
+pub(crate) struct Timeline {
+    pub(crate) enabled: bool,
+    /// Instructions between snapshots
+    pub(crate) interval: u64,
+    /// Snapshots kept, the oldest are dropped first
+    pub(crate) capacity: usize,
+    snapshots: Vec<SaveState>,
+    /// (instruction count, keypad bitmask) for every keypad change, as in movies
+    inputs: Vec<(u64, u16)>,
+    /// Instruction count after the last recorded instruction
+    end: u64,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: 1000,
+            capacity: 1000,
+            snapshots: Vec::new(),
+            inputs: Vec::new(),
+            end: 0,
+        }
+    }
+}
+
+impl Timeline {
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+        self.inputs.clear();
+        self.end = 0;
+    }
+
+    /// Called before every instruction executed live. Returns true if a snapshot should be added first.
+    pub(crate) fn before_step(&mut self, cycles: u64, keypad: u16) -> bool {
+        if cycles < self.end {
+            // Running again from an earlier point, so the recorded future no longer happens
+            self.snapshots.retain(|snapshot| snapshot.cycles <= cycles);
+            self.inputs.retain(|&(at, _)| at < cycles);
+        }
+        if self.inputs.last().map(|&(_, last)| last) != Some(keypad) {
+            self.inputs.push((cycles, keypad));
+        }
+        self.end = cycles + 1;
+        self.snapshots.last().is_none_or(|snapshot| cycles >= snapshot.cycles + self.interval.max(1))
+    }
+
+    pub(crate) fn add_snapshot(&mut self, state: SaveState) {
+        self.snapshots.push(state);
+        if self.snapshots.len() > self.capacity.max(1) {
+            self.snapshots.remove(0);
+            // Keep the keypad state the new oldest snapshot starts with
+            let first = self.snapshots[0].cycles;
+            let first_kept = self.inputs.partition_point(|&(at, _)| at <= first).saturating_sub(1);
+            self.inputs.drain(..first_kept);
+        }
+    }
+
+    /// The latest snapshot taken at or before instruction `cycle`
+    pub(crate) fn snapshot_before(&self, cycle: u64) -> Option<&SaveState> {
+        let count = self.snapshots.partition_point(|snapshot| snapshot.cycles <= cycle);
+        count.checked_sub(1).map(|last| &self.snapshots[last])
+    }
+
+    /// Keypad bitmask while instruction `cycle` was executed
+    pub(crate) fn keypad_at(&self, cycle: u64) -> u16 {
+        let count = self.inputs.partition_point(|&(at, _)| at <= cycle);
+        count.checked_sub(1).map_or(0, |last| self.inputs[last].1)
+    }
+
+    /// Earliest instruction count that can be reached
+    pub(crate) fn start(&self) -> u64 {
+        self.snapshots.first().map_or(0, |snapshot| snapshot.cycles)
+    }
+
+    pub(crate) fn end(&self) -> u64 {
+        self.end
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chip8::Chip8;
+
+    /// Counts up in V0 forever, storing it at 0x300 after a random wait and reading the keypad
+    const ROM: [u8; 18] = [
+        0x70, 0x01, // ADD V0, 1
+        0xC1, 0x03, // RND V1, 3
+        0xA3, 0x00, // LD I, 300
+        0xF0, 0x55, // LD [I], V0
+        0xE2, 0x9E, // SKP V2
+        0x12, 0x00, // JP 200
+        0x71, 0x01, // ADD V1, 1
+        0x12, 0x00, // JP 200
+        0x00, 0x00,
+    ];
+
+    #[test]
+    fn seeks_back_and_reverse_continues_deterministically() {
+        let mut chip8 = Chip8::with_rom(&ROM);
+        chip8.timeline.enabled = true;
+        chip8.timeline.interval = 50;
+
+        let mut hashes = Vec::new();
+        for cycle in 0..400 {
+            chip8.keypad[0] = (cycle / 30) % 2 == 1;
+            hashes.push(chip8.state_hash());
+            chip8.step().unwrap();
+        }
+
+        chip8.seek(123).unwrap();
+        assert_eq!(chip8.cycles, 123);
+        assert_eq!(chip8.state_hash(), hashes[123]);
+        chip8.seek(399).unwrap();
+        assert_eq!(chip8.state_hash(), hashes[399]);
+
+        chip8.breakpoints.insert(0x20C);
+        chip8.reverse_continue().unwrap();
+        assert_eq!(chip8.pc(), 0x20C);
+        let hit = chip8.cycles;
+        assert!(hit < 399);
+        assert_eq!(chip8.state_hash(), hashes[hit as usize]);
+
+        // Running live from the past drops the recorded future
+        chip8.step().unwrap();
+        assert_eq!(chip8.timeline.end(), hit + 1);
+    }
+}
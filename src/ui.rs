@@ -1,46 +1,115 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use egui_macroquad::egui;
 use egui_macroquad::egui::Rect;
 use rfd::FileDialog;
+use crate::access_map::AccessMapView;
+use crate::bus::Watch;
+use crate::capture::{Capture, RecordingFormat};
+use crate::cheats::CheatView;
+use crate::chip8::{Chip8, START_ADDRESS};
+use crate::coverage::{Symbols, EXECUTED, READ, WRITTEN};
+use crate::database::Database;
+use crate::disassembly_view::DisassemblyView;
+use crate::history::HistoryView;
+use crate::library::{latest_save_state, Library, ROM_EXTENSIONS};
+use crate::memory_view::MemoryView;
+use crate::movie::{Movie, MovieState};
+use crate::palette::Palette;
+use crate::profiler::ProfilerView;
+use crate::quirks::Platform;
+use crate::reload::HotReload;
+use crate::rng::RngMode;
+use crate::savestate::SaveState;
+use crate::screen::{DisplayFilter, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::shader::ShaderPreset;
+use crate::speed::{Advance, PlaybackSpeed};
+use crate::sprite_view::SpriteView;
+use crate::state_diff::StateDiffView;
 
 pub(crate) struct Ui {
     pub(crate) reg_edit: egui_memory_editor::MemoryEditor,
-    pub(crate) ram_edit: egui_memory_editor::MemoryEditor,
+    pub(crate) ram_view: MemoryView,
     pub(crate) is_mem_edit_open: bool,
+    sprite_view: SpriteView,
+    is_sprite_view_open: bool,
+    profiler_view: ProfilerView,
+    is_profiler_open: bool,
+    disassembly_view: DisassemblyView,
+    is_disassembly_open: bool,
+    access_map_view: AccessMapView,
+    is_access_map_open: bool,
+    history_view: HistoryView,
+    is_history_open: bool,
+    state_diff_view: StateDiffView,
+    is_state_diff_open: bool,
+    cheat_view: CheatView,
+    is_cheat_view_open: bool,
+    /// Source lines for coverage export
+    symbols: Option<Symbols>,
     pub(crate) rect: Rect,
     pub(crate) rom_path: Option<PathBuf>,
+    pub(crate) capture: Capture,
+    pub(crate) hot_reload: HotReload,
+    /// Address typed into the breakpoint field
+    breakpoint_input: String,
+    /// Address and kind of the next watchpoint
+    watchpoint_input: String,
+    watch: Watch,
+    /// Seed as typed, in hex
+    seed_input: String,
+    pub(crate) library: Library,
+    is_library_open: bool,
+    library_search: String,
+    favourites_only: bool,
+    /// Thumbnails by ROM hash, None if the ROM has no save state
+    thumbnails: HashMap<String, Option<egui::TextureHandle>>,
 }
 
 impl Default for Ui {
     fn default() -> Self {
-        let ram_edit = egui_memory_editor::MemoryEditor::new()
-            .with_address_range("RAM", 0..0x1000)
-            .with_window_title("RAM");
-
         let reg_edit = egui_memory_editor::MemoryEditor::new()
             .with_address_range("Registers", 0..0x10)
             .with_window_title("Registers");
 
         Self {
             reg_edit,
-            ram_edit,
+            ram_view: MemoryView::default(),
             is_mem_edit_open: false,
+            sprite_view: SpriteView::default(),
+            is_sprite_view_open: false,
+            profiler_view: ProfilerView::default(),
+            is_profiler_open: false,
+            disassembly_view: DisassemblyView::default(),
+            is_disassembly_open: false,
+            access_map_view: AccessMapView,
+            is_access_map_open: false,
+            history_view: HistoryView::default(),
+            is_history_open: false,
+            state_diff_view: StateDiffView::default(),
+            is_state_diff_open: false,
+            cheat_view: CheatView::default(),
+            is_cheat_view_open: false,
+            symbols: None,
             rect: Rect::ZERO,
             rom_path: None,
+            capture: Capture::default(),
+            hot_reload: HotReload::default(),
+            breakpoint_input: String::new(),
+            watchpoint_input: String::new(),
+            watch: Watch::Write,
+            seed_input: String::new(),
+            library: Library::open(),
+            is_library_open: false,
+            library_search: String::new(),
+            favourites_only: false,
+            thumbnails: HashMap::new(),
         }
     }
 }
 
 impl Ui {
-    pub(crate) fn render(
-        &mut self,
-        memory: &mut [u8; 4096],
-        registres: &mut [u8; 16],
-        show_grid: &mut bool,
-        cycle_advance: &mut bool,
-        shift_quirk: &mut bool,
-        current_opcode: u16,
-    ) {
+    pub(crate) fn render(&mut self, chip8: &mut Chip8) {
         egui_macroquad::ui(|egui_ctx| {
             let side_panel = egui::SidePanel::right("Debug")
                 .show(egui_ctx, |ui| {
@@ -49,32 +118,345 @@ impl Ui {
 
                     if ui.button("Select ROM").clicked() {
                         let file = FileDialog::new()
-                            .add_filter("CHIP8 ROM", &["ch8"])
+                            .add_filter("CHIP8 ROM", &ROM_EXTENSIONS)
                             .pick_file();
                         self.rom_path = file;
                     }
 
+                    ui.checkbox(&mut self.is_library_open, "Show ROM library");
+
+                    ui.collapsing("ROM info", |ui| {
+                        match chip8.rom_profile {
+                            Some(ref profile) => {
+                                ui.label(format!("Title: {}", profile.title));
+                                ui.label(format!("Platform: {}", profile.platform));
+                                ui.label(format!("Tickrate: {}", profile.tickrate));
+                                for (action, key) in &profile.keys {
+                                    ui.label(format!("{action}: {key:X}"));
+                                }
+                            }
+                            None => {
+                                ui.label("Not found in ROM database");
+                            }
+                        }
+                        if let Some(ref hash) = chip8.rom_hash {
+                            ui.label(format!("SHA-1: {hash}"));
+                        }
+
+                        if let Some(ref analysis) = chip8.rom_analysis {
+                            ui.separator();
+                            ui.label(format!("Size: {} bytes", analysis.size));
+                            ui.label(format!("Reachable instructions: {}", analysis.reachable));
+                            ui.label(format!("SUPER-CHIP instructions: {}", analysis.schip_opcodes.len()));
+                            ui.label(format!("XO-CHIP instructions: {}", analysis.xochip_opcodes.len()));
+                            ui.collapsing("Extended instructions", |ui| {
+                                for (address, opcode) in analysis.schip_opcodes.iter().chain(&analysis.xochip_opcodes) {
+                                    ui.monospace(format!("{address:03X}: {opcode:04X}"));
+                                }
+                            });
+                            let platform = analysis.platform;
+                            ui.label(format!("Likely platform: {platform}"));
+                            if ui.button("Use suggested platform").clicked() {
+                                chip8.set_platform(platform);
+                            }
+                        }
+
+                        ui.checkbox(&mut chip8.auto_profile, "Apply database profile on load")
+                            .on_hover_text(format!("{} ROMs in database", Database::get().len()));
+                        if ui.add_enabled(chip8.rom_profile.is_some(), egui::Button::new("Apply database profile")).clicked() {
+                            chip8.apply_profile();
+                        }
+                    });
+                    ui.collapsing("Patches", |ui| {
+                        patch_ui(ui, chip8);
+                    });
+
                     ui.separator();
 
-                    ui.checkbox(show_grid, "Show grid");
+                    ui.checkbox(&mut chip8.screen.show_grid, "Show grid");
                     ui.checkbox(&mut self.is_mem_edit_open, "Show memory editor");
-                    ui.checkbox(cycle_advance, "Cycle advance")
-                        .on_hover_text("Press 'L' to advance one cycle forward");
+                    ui.checkbox(&mut self.is_sprite_view_open, "Show sprite viewer");
+                    ui.checkbox(&mut self.is_profiler_open, "Show profiler");
+                    ui.checkbox(&mut self.is_disassembly_open, "Show disassembly");
+                    ui.checkbox(&mut self.is_access_map_open, "Show memory access map");
+                    if ui.checkbox(&mut self.is_history_open, "Show instruction history").changed() && self.is_history_open {
+                        // Recording costs two register snapshots per instruction, so only start once it's wanted
+                        chip8.history.enabled = true;
+                    }
+                    ui.checkbox(&mut self.is_state_diff_open, "Show state diff");
+                    ui.checkbox(&mut self.is_cheat_view_open, "Show cheats");
+                    ui.label(format!("Speed: {}", chip8.speed));
+                    ui.collapsing("Speed", |ui| {
+                        ui.horizontal(|ui| {
+                            let label = if chip8.speed.is_paused() { "Resume" } else { "Pause" };
+                            if ui.button(label).on_hover_text("P").clicked() {
+                                chip8.speed.toggle_pause();
+                            }
+                            if ui.button("Frame").on_hover_text("N, advances one frame").clicked() {
+                                chip8.speed.request(Advance::Frame);
+                            }
+                            if ui.button("Cycle").on_hover_text("L, or hold K while paused").clicked() {
+                                chip8.speed.request(Advance::Cycle);
+                            }
+                        });
+                        egui::ComboBox::from_label("Playback speed")
+                            .selected_text(chip8.speed.playback.to_string())
+                            .show_ui(ui, |ui| {
+                                for option in PlaybackSpeed::ALL {
+                                    ui.selectable_value(&mut chip8.speed.playback, option, option.to_string());
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut chip8.speed.turbo_multiplier).clamp_range(2..=64));
+                            ui.label("Turbo multiplier").on_hover_text("Hold Tab to fast-forward");
+                        });
+                    });
+                    ui.collapsing("Timeline", |ui| {
+                        timeline_ui(ui, chip8);
+                    });
+
+                    ui.label(format!("PC: {:03X}, current opcode: {:02X}", chip8.pc(), chip8.opcode));
+
+                    ui.horizontal(|ui| {
+                        ui.label("Tickrate");
+                        ui.add(egui::DragValue::new(&mut chip8.tickrate).clamp_range(1..=10000))
+                            .on_hover_text("Instructions executed per frame");
+                    });
+
+                    let mut platform = chip8.platform;
+                    egui::ComboBox::from_label("Platform")
+                        .selected_text(platform.to_string())
+                        .show_ui(ui, |ui| {
+                            for option in Platform::ALL {
+                                ui.selectable_value(&mut platform, option, option.to_string());
+                            }
+                        });
+                    if platform != chip8.platform {
+                        chip8.set_platform(platform);
+                    }
+
+                    ui.collapsing("Display", |ui| {
+                        let screen = &mut chip8.screen;
+                        ui.checkbox(&mut screen.integer_scaling, "Integer scaling");
 
-                    ui.label(format!("Current opcode: {:02X}", current_opcode));
+                        egui::ComboBox::from_label("Filter")
+                            .selected_text(screen.filter.to_string())
+                            .show_ui(ui, |ui| {
+                                for filter in DisplayFilter::ALL {
+                                    ui.selectable_value(&mut screen.filter, filter, filter.to_string());
+                                }
+                            })
+                            .response
+                            .on_hover_text("Reduces sprite flicker");
+                        if screen.filter == DisplayFilter::Persistence {
+                            ui.add(egui::Slider::new(&mut screen.persistence_frames, 1..=30).text("Decay frames"));
+                        }
+
+                        let post_process = &mut screen.post_process;
+                        let mut preset = post_process.preset;
+                        egui::ComboBox::from_label("Shader")
+                            .selected_text(preset.to_string())
+                            .show_ui(ui, |ui| {
+                                for option in ShaderPreset::ALL {
+                                    ui.selectable_value(&mut preset, option, option.to_string());
+                                }
+                            });
+                        if preset != post_process.preset {
+                            post_process.set_preset(preset);
+                        }
+                        if post_process.preset != ShaderPreset::None {
+                            let params = &mut post_process.params;
+                            let sliders = [
+                                (&mut params.scanlines, "Scanlines"),
+                                (&mut params.curvature, "Curvature"),
+                                (&mut params.bloom, "Bloom"),
+                                (&mut params.vignette, "Vignette"),
+                                (&mut params.lcd_grid, "LCD grid"),
+                            ];
+                            let mut changed = false;
+                            for (value, label) in sliders {
+                                changed |= ui.add(egui::Slider::new(value, 0.0..=1.0).text(label)).changed();
+                            }
+                            if changed {
+                                post_process.preset = ShaderPreset::Custom;
+                            }
+                        }
+
+                        egui::ComboBox::from_label("Palette")
+                            .selected_text(screen.palette.name.clone())
+                            .show_ui(ui, |ui| {
+                                for palette in Palette::builtins() {
+                                    let name = palette.name.clone();
+                                    ui.selectable_value(&mut screen.palette, palette, name);
+                                }
+                            });
+
+                        let labels = ["Background", "Foreground", "Plane 2", "Both planes"];
+                        for (color, label) in screen.palette.colors.iter_mut().zip(labels) {
+                            color_edit(ui, color, label);
+                        }
+                        color_edit(ui, &mut screen.palette.grid, "Grid");
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Import palette").clicked() {
+                                if let Some(path) = FileDialog::new().add_filter("Palette", &["json"]).pick_file() {
+                                    match Palette::load(&path) {
+                                        Ok(palette) => screen.palette = palette,
+                                        Err(e) => log::error!("{e}"),
+                                    }
+                                }
+                            }
+                            if ui.button("Export palette").clicked() {
+                                if let Some(path) = FileDialog::new().add_filter("Palette", &["json"]).save_file() {
+                                    if let Err(e) = screen.palette.save(&path) {
+                                        log::error!("{e}");
+                                    }
+                                }
+                            }
+                        });
+                    });
+
+                    ui.collapsing("Capture", |ui| {
+                        let capture = &mut self.capture;
+                        ui.add_enabled(!capture.is_recording(), egui::Slider::new(&mut capture.scale, 1..=32).text("Scale"));
+                        ui.horizontal(|ui| {
+                            ui.add_enabled_ui(!capture.is_recording(), |ui| {
+                                ui.radio_value(&mut capture.format, RecordingFormat::Gif, "GIF");
+                                ui.radio_value(&mut capture.format, RecordingFormat::RawFrames, "Raw frames");
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Screenshot").on_hover_text("F12").clicked() {
+                                capture.screenshot(&chip8.screen);
+                            }
+                            let label = if capture.is_recording() { "Stop recording" } else { "Record" };
+                            if ui.button(label).on_hover_text("F10").clicked() {
+                                capture.toggle_recording();
+                            }
+                        });
+                    });
+
+                    ui.collapsing("Breakpoints", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut self.breakpoint_input).desired_width(48.0));
+                            let address = u16::from_str_radix(self.breakpoint_input.trim(), 16).ok();
+                            if ui.add_enabled(address.is_some(), egui::Button::new("Add")).clicked() {
+                                chip8.breakpoints.extend(address);
+                                self.breakpoint_input.clear();
+                            }
+                        });
+                        let mut removed = None;
+                        for &address in &chip8.breakpoints {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{address:03X}"));
+                                if ui.small_button("Remove").clicked() {
+                                    removed = Some(address);
+                                }
+                            });
+                        }
+                        if let Some(address) = removed {
+                            chip8.breakpoints.remove(&address);
+                        }
+                    });
+
+                    ui.collapsing("Watchpoints", |ui| {
+                        watchpoints_ui(ui, chip8, &mut self.watchpoint_input, &mut self.watch);
+                    });
+
+                    ui.collapsing("Coverage", |ui| {
+                        coverage_ui(ui, chip8, &mut self.symbols);
+                    });
+
+                    ui.collapsing("Self-modifying code", |ui| {
+                        let mut detect = chip8.smc_enabled();
+                        if ui.checkbox(&mut detect, "Detect").changed() {
+                            chip8.set_smc_enabled(detect);
+                        }
+                        ui.checkbox(&mut chip8.smc_mut().break_on_event, "Pause when detected");
+                        ui.horizontal(|ui| {
+                            ui.label(format!("{} events", chip8.smc().events.len()));
+                            if ui.button("Clear").clicked() {
+                                chip8.smc_mut().clear();
+                            }
+                        });
+                    });
+
+                    ui.collapsing("Hot reload", |ui| {
+                        hot_reload_ui(ui, &mut self.hot_reload, chip8);
+                    });
+
+                    ui.collapsing("Movie", |ui| {
+                        movie_ui(ui, chip8);
+                    });
+
+                    ui.collapsing("Random numbers", |ui| {
+                        let (mut mode, mut seed) = (chip8.rng_mode(), chip8.seed);
+                        egui::ComboBox::from_label("Generator")
+                            .selected_text(mode.to_string())
+                            .show_ui(ui, |ui| {
+                                for option in RngMode::ALL {
+                                    ui.selectable_value(&mut mode, option, option.to_string());
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            let input = egui::TextEdit::singleline(&mut self.seed_input).font(egui::TextStyle::Monospace).desired_width(130.0);
+                            let response = ui.add(input);
+                            if response.lost_focus() {
+                                match u64::from_str_radix(self.seed_input.trim(), 16) {
+                                    Ok(value) => seed = value,
+                                    Err(_) => log::error!("Invalid seed {}, expected up to 16 hex digits", self.seed_input),
+                                }
+                            }
+                            if !response.has_focus() {
+                                self.seed_input = format!("{seed:016X}");
+                            }
+                            ui.label("Seed");
+                            if ui.button("Randomize").clicked() {
+                                seed = rand::random();
+                            }
+                        });
+                        if mode != chip8.rng_mode() || seed != chip8.seed {
+                            chip8.set_rng(mode, seed);
+                        }
+                        if ui.button("Restart ROM").on_hover_text("Restarts with the current seed").clicked() {
+                            chip8.restart();
+                        }
+                    });
+
+                    ui.collapsing("Save states", |ui| {
+                        save_state_ui(ui, chip8);
+                    });
 
                     ui.collapsing("Quirks", |ui| {
-                        ui.checkbox(shift_quirk, "Shift quirk");
+                        let quirks = &mut chip8.quirks;
+                        ui.checkbox(&mut quirks.shift, "Shift quirk");
+                        ui.checkbox(&mut quirks.memory_increment_by_x, "Memory: increment I by X");
+                        ui.checkbox(&mut quirks.memory_leave_i_unchanged, "Memory: leave I unchanged");
+                        ui.checkbox(&mut quirks.wrap, "Wrap sprites");
+                        ui.checkbox(&mut quirks.jump, "Jump to XNN + VX");
+                        ui.checkbox(&mut quirks.vblank, "Wait for vblank");
+                        ui.checkbox(&mut quirks.logic, "Logic ops reset VF");
                     });
                 });
 
-            self.ram_edit.window_ui(
-                egui_ctx, &mut self.is_mem_edit_open, memory,
-                |memory, address| Some(memory[address]),
-                |memory, address, value| memory[address] = value
-            );
+            self.library_window(egui_ctx, chip8);
+
+            self.ram_view.window(egui_ctx, &mut self.is_mem_edit_open, chip8);
+            self.sprite_view.window(egui_ctx, &mut self.is_sprite_view_open, chip8);
+            self.profiler_view.window(egui_ctx, &mut self.is_profiler_open, chip8);
+            self.disassembly_view.window(egui_ctx, &mut self.is_disassembly_open, chip8);
+            self.access_map_view.window(egui_ctx, &mut self.is_access_map_open, chip8);
+            self.state_diff_view.window(egui_ctx, &mut self.is_state_diff_open, chip8);
+            self.cheat_view.window(egui_ctx, &mut self.is_cheat_view_open, chip8);
+            if let Some(address) = self.history_view.window(egui_ctx, &mut self.is_history_open, chip8) {
+                self.disassembly_view.follow_pc = false;
+                self.disassembly_view.jump(address as usize);
+                self.is_disassembly_open = true;
+                self.ram_view.jump(address as usize);
+                self.is_mem_edit_open = true;
+            }
             self.reg_edit.window_ui(
-                egui_ctx, &mut self.is_mem_edit_open, registres,
+                egui_ctx, &mut self.is_mem_edit_open, &mut chip8.registers,
                 |memory, address| Some(memory[address]),
                 |memory, address, value| memory[address] = value
             );
@@ -83,7 +465,399 @@ impl Ui {
         });
     }
 
+    fn library_window(&mut self, ctx: &egui::Context, chip8: &Chip8) {
+        if self.is_library_open {
+            self.library.ensure_scanned();
+        }
+        let mut is_open = self.is_library_open;
+        egui::Window::new("ROM library").open(&mut is_open).show(ctx, |ui| {
+            ui.collapsing("Directories", |ui| {
+                let mut removed = None;
+                for (i, directory) in self.library.settings.directories.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(directory.display().to_string());
+                        if ui.small_button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = removed {
+                    self.library.settings.directories.remove(i);
+                    self.library.save();
+                    self.library.scan();
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Add directory").clicked() {
+                        if let Some(directory) = FileDialog::new().pick_folder() {
+                            self.library.settings.directories.push(directory);
+                            self.library.save();
+                            self.library.scan();
+                        }
+                    }
+                    if ui.button("Rescan").clicked() {
+                        self.library.scan();
+                        self.thumbnails.clear();
+                    }
+                });
+            });
+
+            ui.collapsing("Recent", |ui| {
+                for path in &self.library.settings.recent {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    if ui.link(name).on_hover_text(path.display().to_string()).clicked() {
+                        self.rom_path = Some(path.clone());
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Search");
+                ui.text_edit_singleline(&mut self.library_search);
+                ui.checkbox(&mut self.favourites_only, "Favourites only");
+            });
+
+            let mut toggled = None;
+            egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                egui::Grid::new("library").striped(true).show(ui, |ui| {
+                    for entry in &self.library.entries {
+                        let favourite = self.library.is_favourite(&entry.hash);
+                        if (self.favourites_only && !favourite) || !entry.matches(&self.library_search) {
+                            continue;
+                        }
+
+                        let thumbnail = self.thumbnails.entry(entry.hash.clone())
+                            .or_insert_with(|| thumbnail(ctx, &entry.hash, chip8));
+                        match thumbnail {
+                            Some(texture) => ui.add(egui::Image::new(&*texture).fit_to_exact_size(egui::vec2(64.0, 32.0))),
+                            None => ui.label(""),
+                        };
+
+                        if ui.selectable_label(favourite, if favourite { "★" } else { "☆" }).clicked() {
+                            toggled = Some(entry.hash.clone());
+                        }
+                        let title = ui.label(&entry.title);
+                        if entry.in_database {
+                            title.on_hover_text("From the CHIP-8 database");
+                        }
+                        match entry.platform {
+                            Some(platform) => ui.label(platform.to_string()),
+                            None => ui.label("Octo source"),
+                        };
+                        ui.label(format!("{} bytes", entry.size));
+                        ui.label(&entry.hash[..8]).on_hover_text(&entry.hash);
+
+                        let rom_path = entry.rom_path();
+                        let load = ui.add_enabled(rom_path.is_some(), egui::Button::new("Load"))
+                            .on_disabled_hover_text("Assemble the source next to it first");
+                        if load.clicked() {
+                            if entry.is_source() {
+                                self.hot_reload.source = Some(entry.path.clone());
+                            }
+                            self.rom_path = rom_path;
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+            if let Some(hash) = toggled {
+                self.library.toggle_favourite(&hash);
+            }
+        });
+        self.is_library_open = is_open;
+    }
+
     pub(crate) fn draw(&self) {
         egui_macroquad::draw();
     }
+}
+
+fn movie_ui(ui: &mut egui::Ui, chip8: &mut Chip8) {
+    let movie_dialog = || FileDialog::new().add_filter("chipmate movie", &["c8m"]);
+
+    match chip8.movie {
+        MovieState::Idle => {
+            ui.horizontal(|ui| {
+                if ui.button("Record").on_hover_text("Restarts the ROM and records all input").clicked() {
+                    chip8.start_recording();
+                }
+                if ui.button("Play").clicked() {
+                    if let Some(path) = movie_dialog().pick_file() {
+                        match Movie::load(&path) {
+                            Ok(movie) => chip8.play_movie(movie),
+                            Err(e) => log::error!("{e}"),
+                        }
+                    }
+                }
+                if ui.button("Verify").on_hover_text("Plays a movie instantly and checks its final state").clicked() {
+                    if let Some(path) = movie_dialog().pick_file() {
+                        match Movie::load(&path) {
+                            Ok(movie) => {
+                                chip8.verify_movie(movie);
+                            }
+                            Err(e) => log::error!("{e}"),
+                        }
+                    }
+                }
+            });
+        }
+        MovieState::Recording(ref movie) => {
+            ui.label(format!("Recording, {} inputs", movie.inputs.len()));
+            if ui.button("Stop and save").clicked() {
+                if let Some(movie) = chip8.stop_recording() {
+                    if let Some(path) = movie_dialog().save_file() {
+                        if let Err(e) = movie.save(&path) {
+                            log::error!("{e}");
+                        }
+                    }
+                }
+            }
+        }
+        MovieState::Playing { ref movie, .. } => {
+            ui.label(format!("Playing, {}/{} instructions", chip8.cycles, movie.length));
+            if ui.button("Stop").clicked() {
+                chip8.movie = MovieState::Idle;
+            }
+        }
+    }
+
+    match chip8.movie_verified {
+        Some(true) => ui.label("Last playback matched the recording"),
+        Some(false) => ui.label("Last playback did not match the recording"),
+        None => ui.label("No movie played yet"),
+    };
+}
+
+fn watchpoints_ui(ui: &mut egui::Ui, chip8: &mut Chip8, input: &mut String, watch: &mut Watch) {
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(input).desired_width(48.0));
+        egui::ComboBox::from_id_source("watch")
+            .selected_text(watch.to_string())
+            .show_ui(ui, |ui| {
+                for option in Watch::ALL {
+                    ui.selectable_value(watch, option, option.to_string());
+                }
+            });
+        let address = u16::from_str_radix(input.trim(), 16).ok().filter(|&address| (address as usize) < chip8.memory.len());
+        if ui.add_enabled(address.is_some(), egui::Button::new("Add")).clicked() {
+            chip8.set_watchpoint(address.unwrap_or_default(), *watch);
+            input.clear();
+        }
+    });
+    for (address, watch) in chip8.watchpoints() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{address:03X}  {watch}"));
+            if ui.small_button("Remove").clicked() {
+                chip8.remove_watchpoint(address);
+            }
+        });
+    }
+}
+
+fn coverage_ui(ui: &mut egui::Ui, chip8: &mut Chip8, symbols: &mut Option<Symbols>) {
+    ui.horizontal(|ui| {
+        let mut enabled = chip8.coverage_enabled();
+        if ui.checkbox(&mut enabled, "Enabled").on_hover_text("Shown in the memory editor").changed() {
+            chip8.set_coverage_enabled(enabled);
+        }
+        if ui.button("Clear").clicked() {
+            chip8.coverage_mut().clear();
+        }
+    });
+
+    let program = START_ADDRESS..START_ADDRESS + chip8.rom.len();
+    for (access, name) in [(EXECUTED, "executed"), (READ, "read as data"), (WRITTEN, "written")] {
+        ui.label(format!("{}/{} program bytes {name}", chip8.coverage().count(program.clone(), access), program.len()));
+    }
+
+    ui.horizontal(|ui| {
+        match symbols {
+            Some(ref symbols) => ui.label(format!("{} symbols", symbols.len())),
+            None => ui.label("No symbols"),
+        };
+        if ui.button("Load symbols").on_hover_text("One instruction per line: <hex address> <source file> <line>").clicked() {
+            if let Some(path) = FileDialog::new().pick_file() {
+                match Symbols::load(&path) {
+                    Ok(loaded) => *symbols = Some(loaded),
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+        }
+    });
+    if ui.button("Export lcov").clicked() {
+        if let Some(path) = FileDialog::new().add_filter("lcov tracefile", &["info"]).save_file() {
+            let rom_name = chip8.rom_file.as_ref()
+                .and_then(|path| path.file_name())
+                .map_or("rom.ch8".into(), |name| name.to_string_lossy());
+            let lcov = chip8.coverage().to_lcov(&rom_name, program, symbols.as_ref());
+            if let Err(e) = std::fs::write(path, lcov) {
+                log::error!("{e}");
+            }
+        }
+    }
+}
+
+fn patch_ui(ui: &mut egui::Ui, chip8: &mut Chip8) {
+    let patch_dialog = || FileDialog::new().add_filter("Patch", &["ips", "bps"]);
+
+    ui.checkbox(&mut chip8.auto_patch, "Apply patch next to the ROM on load")
+        .on_hover_text("game.ips or game.bps for game.ch8");
+    if let Some(ref patch) = chip8.patch_file {
+        ui.label(format!("Applied: {}", patch.file_name().unwrap_or_default().to_string_lossy()));
+    }
+    ui.horizontal(|ui| {
+        let rom_file = chip8.rom_file.clone();
+        if ui.add_enabled(rom_file.is_some(), egui::Button::new("Load with patch")).clicked() {
+            if let (Some(rom_file), Some(patch)) = (rom_file, patch_dialog().pick_file()) {
+                // Only start over once the patched ROM has loaded
+                match chip8.load_rom_with_patch(&rom_file, Some(&patch)) {
+                    Ok(()) => chip8.restart(),
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+        }
+        if ui.add_enabled(chip8.rom_file.is_some(), egui::Button::new("Create patch"))
+            .on_hover_text("From memory up to the last changed byte against the unpatched ROM")
+            .clicked()
+        {
+            if let Some(path) = patch_dialog().set_file_name("patch.bps").save_file() {
+                if let Err(e) = chip8.create_patch(&path, None) {
+                    log::error!("{e}");
+                }
+            }
+        }
+    });
+}
+
+fn timeline_ui(ui: &mut egui::Ui, chip8: &mut Chip8) {
+    ui.horizontal(|ui| {
+        if ui.checkbox(&mut chip8.timeline.enabled, "Record").changed() && !chip8.timeline.enabled {
+            chip8.timeline.clear();
+        }
+        ui.add(egui::DragValue::new(&mut chip8.timeline.interval).clamp_range(10..=100_000))
+            .on_hover_text("Instructions between snapshots. Fewer make seeking faster but use more memory.");
+        ui.label("Snapshot interval");
+    });
+    if chip8.timeline.is_empty() {
+        ui.label("Nothing recorded yet");
+        return;
+    }
+
+    let mut cycle = chip8.cycles;
+    let range = chip8.timeline.start()..=chip8.timeline.end();
+    let mut result = Ok(());
+    if ui.add(egui::Slider::new(&mut cycle, range).text("Instruction")).changed() {
+        result = chip8.seek(cycle);
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Step back").clicked() {
+            result = chip8.step_back();
+        }
+        if ui.button("Reverse continue").on_hover_text("Back to the last breakpoint or watchpoint hit").clicked() {
+            result = chip8.reverse_continue();
+        }
+    });
+    if let Err(e) = result {
+        log::error!("{e}");
+    }
+}
+
+fn hot_reload_ui(ui: &mut egui::Ui, hot_reload: &mut HotReload, chip8: &mut Chip8) {
+    ui.checkbox(&mut hot_reload.enabled, "Reload ROM when it changes")
+        .on_hover_text("Also rebuilds it when the Octo source changes");
+    ui.checkbox(&mut hot_reload.keep_breakpoints, "Keep breakpoints");
+
+    ui.horizontal(|ui| {
+        match hot_reload.start_state {
+            Some(ref state) => ui.label(format!("Start state at PC {:03X}", state.pc)),
+            None => ui.label("Start from power-on"),
+        };
+        if ui.button("Use current").clicked() {
+            hot_reload.start_state = Some(chip8.save_state());
+        }
+        if ui.add_enabled(hot_reload.start_state.is_some(), egui::Button::new("Clear")).clicked() {
+            hot_reload.start_state = None;
+        }
+    });
+
+    ui.horizontal(|ui| {
+        let source = hot_reload.source.as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("No Octo source".into(), |name| name.to_string_lossy());
+        ui.label(source);
+        if ui.button("Select").clicked() {
+            if let Some(path) = FileDialog::new().add_filter("Octo source", &["8o"]).pick_file() {
+                hot_reload.source = Some(path);
+            }
+        }
+        if ui.add_enabled(hot_reload.source.is_some(), egui::Button::new("Clear")).clicked() {
+            hot_reload.source = None;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.text_edit_singleline(&mut hot_reload.build_command)
+            .on_hover_text("{source} and {rom} are replaced with the file paths, e.g. octo {source} {rom}");
+        ui.label("Build command");
+    });
+}
+
+fn save_state_ui(ui: &mut egui::Ui, chip8: &mut Chip8) {
+    let state_dialog = || FileDialog::new().add_filter("chipmate save state", &["json"]);
+
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut chip8.state_slot).clamp_range(0..=9));
+        ui.label("Slot");
+        if ui.button("Save").on_hover_text("F5").clicked() {
+            if let Err(e) = chip8.quick_save() {
+                log::error!("{e}");
+            }
+        }
+        if ui.button("Load").on_hover_text("F9").clicked() {
+            if let Err(e) = chip8.quick_load() {
+                log::error!("{e}");
+            }
+        }
+    });
+    ui.horizontal(|ui| {
+        if ui.button("Save to file").clicked() {
+            if let Some(path) = state_dialog().save_file() {
+                if let Err(e) = chip8.save_state().save(&path) {
+                    log::error!("{e}");
+                }
+            }
+        }
+        if ui.button("Load from file").clicked() {
+            if let Some(path) = state_dialog().pick_file() {
+                match SaveState::load(&path) {
+                    Ok(state) => chip8.load_state(&state),
+                    Err(e) => log::error!("{e}"),
+                }
+            }
+        }
+    });
+}
+
+/// Screen of the ROM's latest save state, in the current palette
+fn thumbnail(ctx: &egui::Context, hash: &str, chip8: &Chip8) -> Option<egui::TextureHandle> {
+    let state = latest_save_state(hash)?;
+    let [background, foreground, ..] = chip8.screen.palette.colors.map(|color| {
+        let [r, g, b, _]: [u8; 4] = color.into();
+        egui::Color32::from_rgb(r, g, b)
+    });
+
+    let pixels = state.screen.iter()
+        .flat_map(|&row| (0..SCREEN_WIDTH as usize).rev().map(move |x| row & (1 << x) != 0))
+        .map(|on| if on { foreground } else { background })
+        .collect();
+    let image = egui::ColorImage { size: [SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize], pixels };
+    Some(ctx.load_texture(format!("thumbnail-{hash}"), image, egui::TextureOptions::NEAREST))
+}
+
+fn color_edit(ui: &mut egui::Ui, color: &mut macroquad::color::Color, label: &str) {
+    ui.horizontal(|ui| {
+        let mut rgb = [color.r, color.g, color.b];
+        if ui.color_edit_button_rgb(&mut rgb).changed() {
+            [color.r, color.g, color.b] = rgb;
+        }
+        ui.label(label);
+    });
 }
\ No newline at end of file